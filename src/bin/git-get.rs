@@ -1,7 +1,11 @@
 use anyhow::Result;
 use clap::Parser;
+use git_extend::clone_options;
 use git_extend::commands::get;
-use std::env;
+use git_extend::config::Settings;
+use git_extend::get_base_dir;
+use std::num::NonZeroU32;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "git-get")]
@@ -13,61 +17,107 @@ use std::env;
   git get grdl/git-get
   git get https://github.com/grdl/git-get.git
   git get git@github.com:grdl/git-get.git
-  git get -d path/to/dump/file")]
+  git get gh:grdl/git-get
+  git get https://github.com/grdl/git-get/tree/master
+  git get -d path/to/dump/file
+  git get -d path/to/dump/file -j 8
+  ssh other-host git list -o dump | git get -d -
+  git get --depth 1 --filter blob:none --sparse docs,src gitlab.com/group/monorepo")]
 struct Cli {
     /// Repository to clone
     #[arg(value_name = "REPO")]
     repo: Option<String>,
 
-    /// Branch (or tag) to checkout after cloning
+    /// Branch, tag or full commit id to checkout after cloning. Overrides a ref named in a web URL
     #[arg(short, long)]
     branch: Option<String>,
 
-    /// Path to a dump file listing repos to clone. Ignored when <REPO> argument is used
+    /// Dump file listing repos to clone: a path, a file:// URL or - for stdin, optionally
+    /// gzip-compressed. Ignored when <REPO> argument is used
     #[arg(short, long)]
     dump: Option<String>,
 
-    /// Host to use when <REPO> doesn't have a specified host
-    #[arg(short = 't', long, default_value = "github.com")]
-    host: String,
+    /// Host to use when <REPO> doesn't have a specified host [default: github.com]
+    #[arg(short = 't', long)]
+    host: Option<String>,
 
-    /// Path to repos root where repositories are cloned
-    #[arg(short, long, default_value = "~/repositories")]
-    root: String,
+    /// Path to repos root where repositories are cloned (defaults to $GIT_PATH, the configured root, or ~/repositories)
+    #[arg(short, long)]
+    root: Option<String>,
+
+    /// Scheme to use when <REPO> doesn't have a specified scheme [default: ssh]
+    #[arg(short = 'c', long)]
+    scheme: Option<String>,
 
-    /// Scheme to use when <REPO> doesn't have a specified scheme
-    #[arg(short = 'c', long, default_value = "ssh")]
-    scheme: String,
+    /// Don't create a directory for host; --skip-host=false overrides the configured setting
+    #[arg(
+        short,
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = clap::builder::BoolishValueParser::new()
+    )]
+    skip_host: Option<bool>,
 
-    /// Don't create a directory for host
+    /// Number of repositories to clone in parallel when using a dump file [default: 4]
     #[arg(short, long)]
-    skip_host: bool,
-}
+    jobs: Option<usize>,
 
-fn expand_tilde(path: &str) -> String {
-    if path.starts_with("~/") {
-        if let Ok(home) = env::var("HOME") {
-            return path.replacen("~", &home, 1);
-        }
-    }
-    path.to_string()
+    /// Fetch and fast-forward the repository if it is already cloned
+    #[arg(short, long)]
+    update: bool,
+
+    /// Seconds to wait for the remotes to be fetched when updating
+    #[arg(long, default_value_t = 30, value_name = "SECS")]
+    fetch_timeout: u64,
+
+    /// Only fetch this many commits of history
+    #[arg(long)]
+    depth: Option<NonZeroU32>,
+
+    /// Partial clone filter, e.g. blob:none or tree:0
+    #[arg(long, value_name = "SPEC", value_parser = clone_options::parse_filter)]
+    filter: Option<String>,
+
+    /// Only fetch the branch that is checked out
+    #[arg(long)]
+    single_branch: bool,
+
+    /// Only check out these directories (comma-separated or repeated)
+    #[arg(long, value_name = "DIR", value_delimiter = ',', value_parser = clone_options::parse_sparse_dir)]
+    sparse: Vec<String>,
+
+    /// Initialize and check out submodules after cloning
+    #[arg(long)]
+    recurse_submodules: bool,
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
+    let settings = Settings::load()?;
 
-    let base_dir = if let Ok(git_path) = env::var("GIT_PATH") {
-        git_path
-    } else {
-        expand_tilde(&cli.root)
-    };
+    let base_dir = get_base_dir(cli.root, &settings)?;
 
     let config = get::Config {
         base_dir,
         branch: cli.branch,
-        default_host: cli.host,
-        default_scheme: cli.scheme,
-        skip_host: cli.skip_host,
+        default_host: cli.host.unwrap_or(settings.host),
+        default_scheme: cli.scheme.unwrap_or(settings.scheme),
+        skip_host: cli.skip_host.unwrap_or(settings.skip_host),
+        jobs: cli.jobs.unwrap_or(settings.jobs),
+        aliases: settings.aliases,
+        url_rewrites: settings.url_rewrites,
+        update: cli.update,
+        fetch_timeout: Duration::from_secs(cli.fetch_timeout),
+        clone_options: clone_options::CloneOptions {
+            depth: cli.depth,
+            filter: cli.filter,
+            single_branch: cli.single_branch,
+            sparse: cli.sparse,
+            submodules: cli.recurse_submodules,
+        },
     };
 
     if let Some(dump_file) = cli.dump {
@@ -1,23 +1,111 @@
 use anyhow::Result;
 use clap::Parser;
-use git_extend::{commands, get_base_dir};
+use git_extend::config::Settings;
+use git_extend::{cache, commands, get_base_dir};
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "git-list")]
 #[command(about = "List all git repositories and their status")]
 struct Cli {
-    /// Output format: tree, flat, or dump
-    #[arg(short, long, default_value = "tree")]
-    output: String,
+    /// Output format: tree, flat, dump, json, or ndjson [default: tree]
+    #[arg(short, long)]
+    output: Option<String>,
+
+    /// When to use colors: auto, always, or never [default: auto]
+    #[arg(long, value_name = "WHEN")]
+    color: Option<String>,
 
-    /// Root directory to search for repositories (defaults to $GIT_PATH)
+    /// Root directory to search for repositories (defaults to $GIT_PATH, the configured root, or ~/repositories)
     #[arg(short, long)]
     dir: Option<String>,
+
+    /// Fetch all remotes of each repository before computing its status
+    #[arg(short, long)]
+    fetch: bool,
+
+    /// Seconds to wait for a repository's remotes to be fetched
+    #[arg(long, default_value_t = 30, value_name = "SECS")]
+    fetch_timeout: u64,
+
+    /// Only show repositories with uncommitted or untracked changes
+    #[arg(long)]
+    dirty: bool,
+
+    /// Only show repositories with branches ahead of their upstream
+    #[arg(long)]
+    ahead: bool,
+
+    /// Only show repositories with branches behind their upstream
+    #[arg(long)]
+    behind: bool,
+
+    /// Only show repositories with branches that have no upstream or whose upstream is gone
+    #[arg(long)]
+    no_upstream: bool,
+
+    /// Only show repositories that are not clean and in sync with their upstream
+    #[arg(long)]
+    only_problems: bool,
+
+    /// Search the root and re-evaluate every repository instead of reusing the index of the previous run
+    #[arg(long)]
+    refresh: bool,
+
+    /// Skip directories matching this gitignore-style pattern, relative to the root (repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    exclude: Vec<String>,
+
+    /// Search directories matching this pattern even if they are excluded (repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    include: Vec<String>,
+
+    /// Only look for repositories up to this many directory levels below the root
+    #[arg(long, value_name = "N")]
+    max_depth: Option<usize>,
+
+    /// Also look for repositories inside other repositories' working trees
+    #[arg(long)]
+    nested: bool,
+
+    /// Search symlinked directories too, visiting each directory only once
+    #[arg(long)]
+    follow_symlinks: bool,
+
+    /// Show the submodules of each repository and whether they are initialized, at the recorded commit and clean
+    #[arg(long)]
+    submodules: bool,
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
-    let base_dir = get_base_dir(cli.dir)?;
-    commands::list::execute(&cli.output, &base_dir)
+    let settings = Settings::load()?;
+    let base_dir = get_base_dir(cli.dir, &settings)?;
+
+    let config = commands::list::Config {
+        output_format: cli.output.unwrap_or(settings.output),
+        color: cli.color.unwrap_or(settings.color),
+        base_dir,
+        fetch: cli.fetch.then(|| Duration::from_secs(cli.fetch_timeout)),
+        filter: commands::list::Filter {
+            dirty: cli.dirty,
+            ahead: cli.ahead,
+            behind: cli.behind,
+            no_upstream: cli.no_upstream,
+            only_problems: cli.only_problems,
+        },
+        discovery: commands::list::Discovery {
+            exclude: cli.exclude,
+            include: cli.include,
+            max_depth: cli.max_depth,
+            nested: cli.nested,
+            follow_symlinks: cli.follow_symlinks,
+            submodules: cli.submodules,
+        },
+        cache_dir: cache::default_dir(),
+        refresh: cli.refresh,
+    };
+
+    commands::list::execute(&config)
 }
 
@@ -0,0 +1,59 @@
+use anyhow::Result;
+use clap::Parser;
+use git_extend::config::Settings;
+use git_extend::{commands, get_base_dir};
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(name = "git-sync")]
+#[command(about = "Fetch every repository and fast-forward branches that are behind their upstream")]
+struct Cli {
+    /// Root directory to search for repositories (defaults to $GIT_PATH, the configured root, or ~/repositories)
+    #[arg(short, long)]
+    dir: Option<String>,
+
+    /// Seconds to wait for a repository's remotes to be fetched
+    #[arg(long, default_value_t = 30, value_name = "SECS")]
+    fetch_timeout: u64,
+
+    /// Skip directories matching this gitignore-style pattern, relative to the root (repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    exclude: Vec<String>,
+
+    /// Search directories matching this pattern even if they are excluded (repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    include: Vec<String>,
+
+    /// Only look for repositories up to this many directory levels below the root
+    #[arg(long, value_name = "N")]
+    max_depth: Option<usize>,
+
+    /// Also look for repositories inside other repositories' working trees
+    #[arg(long)]
+    nested: bool,
+
+    /// Search symlinked directories too, visiting each directory only once
+    #[arg(long)]
+    follow_symlinks: bool,
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let settings = Settings::load()?;
+    let base_dir = get_base_dir(cli.dir, &settings)?;
+
+    let config = commands::sync::Config {
+        base_dir,
+        fetch_timeout: Duration::from_secs(cli.fetch_timeout),
+        discovery: commands::list::Discovery {
+            exclude: cli.exclude,
+            include: cli.include,
+            max_depth: cli.max_depth,
+            nested: cli.nested,
+            follow_symlinks: cli.follow_symlinks,
+            ..Default::default()
+        },
+    };
+
+    commands::sync::execute(&config)
+}
@@ -0,0 +1,226 @@
+use crate::commands::list::RepoStatus;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Bumped whenever the index layout changes. Indexes of other versions are ignored.
+const VERSION: u32 = 3;
+
+/// The modification time of a file or directory at the time it was looked at.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stamp {
+    path: PathBuf,
+    /// Nanoseconds since the Unix epoch, or `None` if the path didn't exist.
+    modified: Option<u64>,
+}
+
+impl Stamp {
+    pub fn of(path: &Path) -> Stamp {
+        Stamp::new(path, fs::metadata(path).ok().as_ref())
+    }
+
+    pub fn new(path: &Path, metadata: Option<&fs::Metadata>) -> Stamp {
+        let modified = metadata
+            .and_then(|metadata| metadata.modified().ok())
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .and_then(|duration| u64::try_from(duration.as_nanos()).ok());
+        Stamp {
+            path: path.to_path_buf(),
+            modified,
+        }
+    }
+
+    fn is_current(&self) -> bool {
+        *self == Stamp::of(&self.path)
+    }
+}
+
+/// The status of a repository along with the state it was computed from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub status: RepoStatus,
+    stamps: Vec<Stamp>,
+}
+
+impl Entry {
+    /// Evaluate the repository at `path` with `load`. Its state is recorded first, so changes
+    /// made while `load` runs invalidate the entry.
+    pub fn load(path: &Path, load: impl FnOnce(&Path) -> Result<RepoStatus>) -> Result<Entry> {
+        let stamps = repo_stamps(path);
+        Ok(Entry {
+            status: load(path)?,
+            stamps,
+        })
+    }
+
+    fn is_current(&self) -> bool {
+        self.stamps.iter().all(Stamp::is_current)
+    }
+}
+
+/// Repositories discovered under a root, and the directories that were searched to find them.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Index {
+    version: u32,
+    root: PathBuf,
+    /// The discovery options the repositories were found with.
+    options: String,
+    directories: Vec<Stamp>,
+    /// Every repository found, including those that couldn't be evaluated. Sorted.
+    paths: Vec<PathBuf>,
+    /// The repositories that were evaluated, sorted by path.
+    repositories: Vec<Entry>,
+}
+
+impl Index {
+    pub fn new(
+        root: &Path,
+        options: String,
+        directories: Vec<Stamp>,
+        mut paths: Vec<PathBuf>,
+        mut repositories: Vec<Entry>,
+    ) -> Index {
+        paths.sort();
+        repositories.sort_by(|a, b| a.status.path.cmp(&b.status.path));
+        Index {
+            version: VERSION,
+            root: root.to_path_buf(),
+            options,
+            directories,
+            paths,
+            repositories,
+        }
+    }
+
+    /// The index of `root` saved in `cache_dir`, or `None` if there is none that was built with
+    /// the same `options`.
+    pub fn load(cache_dir: &Path, root: &Path, options: &str) -> Option<Index> {
+        let content = fs::read(index_path(cache_dir, root)).ok()?;
+        let index: Index = serde_json::from_slice(&content).ok()?;
+        (index.version == VERSION && index.root == root && index.options == options).then_some(index)
+    }
+
+    pub fn save(&self, cache_dir: &Path) -> Result<()> {
+        let path = index_path(cache_dir, &self.root);
+        fs::create_dir_all(cache_dir).with_context(|| format!("Failed to create {}", cache_dir.display()))?;
+
+        // Replace the index in one step, so a concurrent run never reads half of it
+        let temp = path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&temp, serde_json::to_vec(self)?).with_context(|| format!("Failed to write {}", temp.display()))?;
+        fs::rename(&temp, &path).with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Whether none of the searched directories changed since, so searching again would find
+    /// the same repositories.
+    pub fn is_current(&self) -> bool {
+        self.directories.iter().all(Stamp::is_current)
+    }
+
+    pub fn directories(&self) -> &[Stamp] {
+        &self.directories
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.paths.iter().map(PathBuf::as_path)
+    }
+
+    /// The entry of the repository at `path`, unless the repository changed since.
+    pub fn entry(&self, path: &Path) -> Option<&Entry> {
+        let position = self
+            .repositories
+            .binary_search_by(|entry| entry.status.path.as_path().cmp(path))
+            .ok()?;
+        Some(&self.repositories[position]).filter(|entry| entry.is_current())
+    }
+
+    pub fn into_statuses(self) -> Vec<RepoStatus> {
+        self.repositories.into_iter().map(|entry| entry.status).collect()
+    }
+}
+
+/// The cache directory: `git-extend` under `$XDG_CACHE_HOME` (falling back to `~/.cache`).
+pub fn default_dir() -> Option<PathBuf> {
+    let cache_dir = env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(cache_dir.join("git-extend"))
+}
+
+fn index_path(cache_dir: &Path, root: &Path) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    root.hash(&mut hasher);
+    cache_dir.join(format!("index-{:016x}.json", hasher.finish()))
+}
+
+/// What the ref-derived part of the status of the repository at `path` is computed from: its
+/// `HEAD`, index, config and references. Working-tree changes aren't covered, as they are
+/// counted again on every run.
+fn repo_stamps(path: &Path) -> Vec<Stamp> {
+    let Ok(repo) = gix::open(path) else {
+        return vec![Stamp::of(path)];
+    };
+    let (git_dir, common_dir) = (repo.git_dir(), repo.common_dir());
+    let mut stamps: Vec<_> = [
+        git_dir.join("HEAD"),
+        git_dir.join("index"),
+        common_dir.join("config"),
+        common_dir.join("packed-refs"),
+    ]
+    .iter()
+    .map(|file| Stamp::of(file))
+    .collect();
+    add_dir_stamps(&common_dir.join("refs"), &mut stamps);
+    stamps
+}
+
+fn add_dir_stamps(dir: &Path, stamps: &mut Vec<Stamp>) {
+    stamps.push(Stamp::of(dir));
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            add_dir_stamps(&entry.path(), stamps);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::list::load_repo_status;
+    use crate::test_support::{init_source_repo, temp_dir};
+
+    #[test]
+    fn test_index_invalidation() {
+        let dir = temp_dir("cache-index");
+        let (root, cache_dir) = (dir.join("root"), dir.join("cache"));
+        let repo_path = root.join("a");
+        let head = init_source_repo(&repo_path);
+
+        let entry = Entry::load(&repo_path, |path| load_repo_status(path, None)).unwrap();
+        Index::new(&root, "options".to_string(), vec![Stamp::of(&root)], vec![repo_path.clone()], vec![entry])
+            .save(&cache_dir)
+            .unwrap();
+        assert!(Index::load(&cache_dir, &root, "other options").is_none());
+
+        let index = Index::load(&cache_dir, &root, "options").unwrap();
+        assert!(index.is_current());
+        assert_eq!(index.paths().collect::<Vec<_>>(), [repo_path.as_path()]);
+        assert!(index.entry(&repo_path).is_some());
+
+        gix::open(&repo_path)
+            .unwrap()
+            .reference("refs/heads/new", head, gix::refs::transaction::PreviousValue::Any, "create new")
+            .unwrap();
+        assert!(index.entry(&repo_path).is_none());
+
+        fs::create_dir(root.join("b")).unwrap();
+        assert!(!index.is_current());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,63 @@
+use std::num::NonZeroU32;
+
+/// How much of a repository to fetch and check out.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CloneOptions {
+    /// Only fetch this many commits of history.
+    pub depth: Option<NonZeroU32>,
+    /// Partial clone filter, e.g. `blob:none` or `tree:0`.
+    pub filter: Option<String>,
+    /// Only fetch the branch that is checked out.
+    pub single_branch: bool,
+    /// Directories to check out in cone mode. Everything is checked out when empty.
+    pub sparse: Vec<String>,
+    /// Initialize and check out submodules, recursively.
+    pub submodules: bool,
+}
+
+impl CloneOptions {
+    /// These options, with anything left unset taken from `defaults`.
+    pub(crate) fn or(&self, defaults: &CloneOptions) -> CloneOptions {
+        CloneOptions {
+            depth: self.depth.or(defaults.depth),
+            filter: self.filter.clone().or_else(|| defaults.filter.clone()),
+            single_branch: self.single_branch || defaults.single_branch,
+            sparse: if self.sparse.is_empty() {
+                defaults.sparse.clone()
+            } else {
+                self.sparse.clone()
+            },
+            submodules: self.submodules || defaults.submodules,
+        }
+    }
+}
+
+/// Validate a partial clone filter spec, accepting the forms git supports for clones.
+pub fn parse_filter(spec: &str) -> Result<String, String> {
+    let valid = match spec.split_once(':') {
+        Some(("blob", "none")) => true,
+        Some(("blob", limit)) => limit
+            .strip_prefix("limit=")
+            .is_some_and(|size| size.trim_end_matches(['k', 'm', 'g']).parse::<u64>().is_ok()),
+        Some(("tree", depth)) => depth.parse::<u64>().is_ok(),
+        _ => false,
+    };
+
+    if valid {
+        Ok(spec.to_string())
+    } else {
+        Err(format!(
+            "invalid filter '{}', expected blob:none, blob:limit=<size> or tree:<depth>",
+            spec
+        ))
+    }
+}
+
+/// Normalize a sparse checkout directory to a relative path without surrounding slashes.
+pub fn parse_sparse_dir(dir: &str) -> Result<String, String> {
+    let dir = dir.trim_matches('/');
+    if dir.is_empty() || dir.split('/').any(|c| matches!(c, "" | "." | "..")) {
+        return Err(format!("invalid sparse checkout directory '{}'", dir));
+    }
+    Ok(dir.to_string())
+}
@@ -1,19 +1,366 @@
+use crate::clone_options::CloneOptions;
+use crate::commands::sync;
+use crate::dump;
 use crate::url_parser::{RepoInfo, parse_repo_url};
+use crate::worktree;
 use anyhow::{Context, Result};
+use gix::bstr::ByteSlice;
+use std::collections::{BTreeMap, HashMap, HashSet, hash_map};
+use std::fmt;
 use std::fs;
+use std::io::{self, Read};
+use std::panic;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
 
+#[derive(Clone)]
 pub struct Config {
     pub base_dir: String,
     pub branch: Option<String>,
     pub default_host: String,
     pub default_scheme: String,
     pub skip_host: bool,
+    pub jobs: usize,
+    pub aliases: BTreeMap<String, String>,
+    pub url_rewrites: Vec<(String, String)>,
+    /// Fetch and fast-forward repositories that are already cloned.
+    pub update: bool,
+    pub fetch_timeout: Duration,
+    pub clone_options: CloneOptions,
 }
 
 pub fn execute(url: &str, config: &Config) -> Result<()> {
-    let mut repo_info = parse_repo_url(url).context("Failed to parse repository URL")?;
+    let (repo_info, clone_path) = resolve_repo(url, config)?;
+
+    if clone_path.exists() {
+        match use_existing(&repo_info, &clone_path, config)? {
+            Some(detail) => println!("Already present at {}: {}", clone_path.display(), detail),
+            None => println!("Already present at {}", clone_path.display()),
+        }
+        return Ok(());
+    }
+
+    println!("Cloning into {}", clone_path.display());
+
+    let branch = checkout_ref(config.branch.clone(), &repo_info)?;
+    clone_into(&repo_info, &clone_path, &branch, &config.clone_options)?;
+
+    println!(
+        "Successfully cloned repository to: {}",
+        clone_path.display()
+    );
+    Ok(())
+}
+
+enum DumpOutcome {
+    Cloned(PathBuf),
+    Present(PathBuf, Option<String>),
+    Failed(anyhow::Error),
+}
+
+pub fn execute_dump(dump_file: &str, config: &Config) -> Result<()> {
+    let content = read_dump(dump_file)?;
+    let mut entries = dump::parse(&content)?;
+    let mut seen = HashSet::new();
+    entries.retain(|entry| seen.insert(entry.clone()));
+
+    let mut outcomes: Vec<Option<DumpOutcome>> = entries.iter().map(|_| None).collect();
+    let mut pending = Vec::new();
+    let mut claimed: HashMap<PathBuf, usize> = HashMap::new();
+    for (index, entry) in entries.iter().enumerate() {
+        // Two workers cloning into the same directory would trample each other
+        let Ok((_, path)) = resolve_dump_entry(entry, config) else {
+            pending.push(index);
+            continue;
+        };
+        match claimed.entry(path) {
+            hash_map::Entry::Occupied(other) => {
+                let error = anyhow::anyhow!("Clone path is already used by {}", entries[*other.get()].url);
+                eprintln!("✗ Failed to clone {}: {:#}", entry.url, error);
+                outcomes[index] = Some(DumpOutcome::Failed(error));
+            }
+            hash_map::Entry::Vacant(slot) => {
+                slot.insert(index);
+                pending.push(index);
+            }
+        }
+    }
+
+    let next = AtomicUsize::new(0);
+    let (tx, rx) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..config.jobs.clamp(1, pending.len().max(1)) {
+            let tx = tx.clone();
+            let (next, entries, pending) = (&next, &entries, &pending);
+            scope.spawn(move || {
+                while let Some(&index) = pending.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let outcome = isolated(|| clone_dump_entry(&entries[index], config));
+                    if tx.send((index, outcome)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(tx);
+
+        for (index, outcome) in rx {
+            let url = &entries[index].url;
+            match &outcome {
+                DumpOutcome::Cloned(_) => println!("✓ Cloned {}", url),
+                DumpOutcome::Present(_, Some(detail)) => println!("- {} already present: {}", url, detail),
+                DumpOutcome::Present(_, None) => println!("- {} already present", url),
+                DumpOutcome::Failed(e) => eprintln!("✗ Failed to clone {}: {:#}", url, e),
+            }
+            outcomes[index] = Some(outcome);
+        }
+    });
+
+    let outcomes: Vec<DumpOutcome> = outcomes.into_iter().flatten().collect();
+    print_dump_summary(&entries, &outcomes);
+
+    let failed = outcomes
+        .iter()
+        .filter(|o| matches!(o, DumpOutcome::Failed(_)))
+        .count();
+    if failed > 0 {
+        return Err(anyhow::anyhow!(
+            "{} of {} repositories failed to clone",
+            failed,
+            entries.len()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Run `clone` so that a panic fails only its own entry instead of the whole dump.
+fn isolated(clone: impl FnOnce() -> DumpOutcome) -> DumpOutcome {
+    panic::catch_unwind(panic::AssertUnwindSafe(clone)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown error".to_string());
+        DumpOutcome::Failed(anyhow::anyhow!("Cloning panicked: {}", message))
+    })
+}
+
+/// Read a dump from `source`: a path, a `file://` URL, or `-` for stdin. Gzip-compressed dumps
+/// are decompressed.
+fn read_dump(source: &str) -> Result<String> {
+    let bytes = if source == "-" {
+        let mut bytes = Vec::new();
+        io::stdin()
+            .read_to_end(&mut bytes)
+            .context("Failed to read dump from stdin")?;
+        bytes
+    } else {
+        let path = match source.strip_prefix("file://") {
+            Some(url) => {
+                // Only the local host can be named, as `file:///path` or `file://localhost/path`
+                let (host, path) = url.split_at(url.find('/').unwrap_or(url.len()));
+                if !(host.is_empty() || host == "localhost") || path.is_empty() {
+                    return Err(anyhow::anyhow!("Dump URL {} does not name a local file", source));
+                }
+                path
+            }
+            None => source,
+        };
+        fs::read(path).with_context(|| format!("Failed to read dump file {}", path))?
+    };
+
+    let bytes = if bytes.starts_with(&[0x1f, 0x8b]) {
+        let mut content = Vec::new();
+        flate2::read::MultiGzDecoder::new(bytes.as_slice())
+            .read_to_end(&mut content)
+            .context("Failed to decompress dump file")?;
+        content
+    } else {
+        bytes
+    };
+
+    String::from_utf8(bytes).context("Dump file is not valid UTF-8")
+}
+
+fn clone_dump_entry(entry: &dump::Entry, config: &Config) -> DumpOutcome {
+    let (repo_info, clone_path) = match resolve_dump_entry(entry, config) {
+        Ok(resolved) => resolved,
+        Err(e) => return DumpOutcome::Failed(e),
+    };
+
+    let options = entry.clone_options().or(&config.clone_options);
+
+    if clone_path.exists() {
+        return match use_existing(&repo_info, &clone_path, config) {
+            Ok(detail) => DumpOutcome::Present(clone_path, detail),
+            Err(e) => DumpOutcome::Failed(e),
+        };
+    }
+
+    let branch = match checkout_ref(entry.branch.clone().or_else(|| config.branch.clone()), &repo_info) {
+        Ok(branch) => branch,
+        Err(e) => return DumpOutcome::Failed(e),
+    };
+
+    // The clone is removed if its state can't be restored, so the entry is retried next time
+    let cloned = clone_into(&repo_info, &clone_path, &branch, &options).and_then(|()| {
+        restore_dump_state(entry, &clone_path, options.submodules).inspect_err(|_| {
+            let _ = fs::remove_dir_all(&clone_path);
+        })
+    });
+    match cloned {
+        Ok(()) => DumpOutcome::Cloned(clone_path),
+        Err(e) => DumpOutcome::Failed(e),
+    }
+}
+
+/// Resolve a dump entry like a command-line URL, except that an explicit path wins. URLs that
+/// can't be parsed, such as local paths, are fine as long as the entry has a path.
+fn resolve_dump_entry(entry: &dump::Entry, config: &Config) -> Result<(RepoInfo, PathBuf)> {
+    let Some(path) = &entry.path else {
+        return resolve_repo(&entry.url, config);
+    };
+
+    let repo_info = match resolve_repo(&entry.url, config) {
+        Ok((repo_info, _)) => repo_info,
+        Err(_) => RepoInfo {
+            full_url: rewrite_url(&entry.url, &config.url_rewrites),
+            ..Default::default()
+        },
+    };
+    Ok((repo_info, Path::new(&config.base_dir).join(path)))
+}
+
+/// Bring a fresh clone to the state recorded in its dump entry: the pinned commit and any
+/// remotes besides `origin`.
+fn restore_dump_state(entry: &dump::Entry, clone_path: &Path, submodules: bool) -> Result<()> {
+    if let Some(commit) = &entry.commit
+        && pin_commit(clone_path, gix::ObjectId::from_hex(commit.as_bytes())?, false)?
+        && submodules
+    {
+        update_submodules(clone_path)?;
+    }
+
+    add_remotes(clone_path, &entry.remotes)
+}
+
+/// Check out `commit` in the clone at `path`, moving the checked-out branch along or, with
+/// `detach`, detaching `HEAD` at it. Returns whether anything changed.
+fn pin_commit(path: &Path, commit: gix::ObjectId, detach: bool) -> Result<bool> {
+    let mut repo = gix::open(path)?;
+    repo.committer_or_set_generic_fallback()?;
+    let head = repo.head_id()?.detach();
+    if head == commit && !detach {
+        return Ok(false);
+    }
+
+    if repo.find_commit(commit).is_err() {
+        deepen_until(&repo, commit).with_context(|| format!("Commit {} is not in the cloned history", commit))?;
+    }
+    worktree::update_worktree(&repo, commit)?;
+
+    let (head_ref, previous) = match repo.head_name()? {
+        Some(name) if !detach => (
+            name.as_bstr().to_string(),
+            gix::refs::transaction::PreviousValue::MustExistAndMatch(head.into()),
+        ),
+        _ => ("HEAD".to_string(), gix::refs::transaction::PreviousValue::Any),
+    };
+    repo.reference(head_ref.as_str(), commit, previous, "get: check out commit")?;
+    Ok(true)
+}
+
+/// Fetch more history into the shallow clone `repo` until it contains `commit`, doubling the
+/// number of commits fetched each round.
+fn deepen_until(repo: &gix::Repository, commit: gix::ObjectId) -> Result<()> {
+    let mut deepen = 1u32;
+    while repo.is_shallow() {
+        repo.find_remote("origin")?
+            .connect(gix::remote::Direction::Fetch)?
+            .prepare_fetch(gix::progress::Discard, Default::default())?
+            .with_shallow(gix::remote::fetch::Shallow::Deepen(deepen))
+            .receive(gix::progress::Discard, &gix::interrupt::IS_INTERRUPTED)?;
+        if repo.find_commit(commit).is_ok() {
+            return Ok(());
+        }
+        if deepen == u32::MAX {
+            break;
+        }
+        deepen = deepen.saturating_mul(2);
+    }
+    repo.find_commit(commit)?;
+    Ok(())
+}
+
+fn add_remotes(path: &Path, remotes: &BTreeMap<String, String>) -> Result<()> {
+    if remotes.is_empty() {
+        return Ok(());
+    }
+
+    let repo = gix::open(path)?;
+    edit_local_config(&repo, |config| {
+        for (name, url) in remotes.iter().filter(|(name, _)| name.as_str() != "origin") {
+            let refspec = format!("+refs/heads/*:refs/remotes/{}/*", name);
+            repo.remote_at(url.as_str())?
+                .with_refspecs([refspec.as_str()], gix::remote::Direction::Fetch)?
+                .save_as_to(name.as_str(), config)
+                .with_context(|| format!("Failed to add remote {}", name))?;
+        }
+        Ok(())
+    })
+}
+
+/// Apply `edit` to the repository's own config file and write it back.
+fn edit_local_config(
+    repo: &gix::Repository,
+    edit: impl FnOnce(&mut gix::config::File<'static>) -> Result<()>,
+) -> Result<()> {
+    let config_path = repo.git_dir().join("config");
+    let mut config = gix::config::File::from_path_no_includes(config_path.clone(), gix::config::Source::Local)?;
+    edit(&mut config)?;
+    fs::write(config_path, config.to_bstring())?;
+    Ok(())
+}
+
+fn print_dump_summary(entries: &[dump::Entry], outcomes: &[DumpOutcome]) {
+    let width = entries.iter().map(|e| e.url.len()).max().unwrap_or(0);
+    let (mut cloned, mut present, mut failed) = (0, 0, 0);
+
+    println!();
+    println!("{:<8} {:<width$} DETAIL", "STATUS", "REPOSITORY", width = width);
+    for (entry, outcome) in entries.iter().zip(outcomes) {
+        let (status, detail) = match outcome {
+            DumpOutcome::Cloned(path) => {
+                cloned += 1;
+                ("cloned", path.display().to_string())
+            }
+            DumpOutcome::Present(path, detail) => {
+                present += 1;
+                let detail = match detail {
+                    Some(detail) => format!("{}: {}", path.display(), detail),
+                    None => path.display().to_string(),
+                };
+                ("present", detail)
+            }
+            DumpOutcome::Failed(e) => {
+                failed += 1;
+                ("failed", format!("{:#}", e))
+            }
+        };
+        println!("{:<8} {:<width$} {}", status, entry.url, detail, width = width);
+    }
+    println!();
+    println!("{} cloned, {} already present, {} failed", cloned, present, failed);
+}
+
+fn resolve_repo(url: &str, config: &Config) -> Result<(RepoInfo, PathBuf)> {
+    let url = expand_alias(url, &config.aliases);
+    let mut repo_info = parse_repo_url(&url).context("Failed to parse repository URL")?;
 
     // Apply default host if needed
     if repo_info.host.is_empty() {
@@ -21,60 +368,162 @@ pub fn execute(url: &str, config: &Config) -> Result<()> {
     }
 
     // Update URL based on scheme preference
-    if !url.contains("://") && !url.starts_with("git@") {
+    if repo_info.scheme.is_none() {
         repo_info.full_url = build_url(&repo_info, &config.default_scheme);
     }
 
+    // The clone path stays keyed to the canonical host, only the fetch URL is rewritten
+    repo_info.full_url = rewrite_url(&repo_info.full_url, &config.url_rewrites);
+
     let clone_path = get_clone_path(&repo_info, &config.base_dir, config.skip_host);
+    Ok((repo_info, clone_path))
+}
 
-    if clone_path.exists() {
-        return Err(anyhow::anyhow!(
-            "Repository already exists at: {}",
-            clone_path.display()
-        ));
+/// The ref to check out: `explicit` if given, else the commit or branch named by a web URL.
+fn checkout_ref(explicit: Option<String>, repo_info: &RepoInfo) -> Result<Option<String>> {
+    if explicit.is_some() {
+        return Ok(explicit);
     }
+    if let Some(commit) = &repo_info.commit {
+        return Ok(Some(commit.clone()));
+    }
+    repo_info
+        .branch
+        .as_deref()
+        .map(|branch| resolve_web_branch(&repo_info.full_url, branch))
+        .transpose()
+}
 
-    if let Some(parent) = clone_path.parent() {
-        fs::create_dir_all(parent).context("Failed to create parent directories")?;
+/// Find where the branch ends in `reference`, the part of a web URL that starts with a branch or
+/// tag and may continue with a path within the repository, as in `.../tree/main/docs`. The
+/// longest prefix that names a branch or tag of the remote wins; if none does, `reference` is
+/// returned as is for the clone to report.
+fn resolve_web_branch(url: &str, reference: &str) -> Result<String> {
+    if !reference.contains('/') {
+        return Ok(reference.to_string());
     }
 
-    println!("Cloning into {}", clone_path.display());
+    let refs = remote_refs(url).context("Failed to list the branches of the remote")?;
+    let mut candidate = reference;
+    loop {
+        if refs.iter().any(|name| name == candidate) {
+            return Ok(candidate.to_string());
+        }
+        match candidate.rsplit_once('/') {
+            Some((shorter, _)) => candidate = shorter,
+            None => return Ok(reference.to_string()),
+        }
+    }
+}
 
-    clone_repository(&repo_info.full_url, &clone_path, &config.branch)?;
+/// The short names of the branches and tags of the remote at `url`.
+fn remote_refs(url: &str) -> Result<Vec<String>> {
+    // A remote belongs to a repository, so a throwaway one is needed to talk to the server
+    static SCRATCH: AtomicUsize = AtomicUsize::new(0);
+    let scratch = std::env::temp_dir().join(format!(
+        "git-get-{}-{}",
+        std::process::id(),
+        SCRATCH.fetch_add(1, Ordering::Relaxed)
+    ));
 
-    println!(
-        "Successfully cloned repository to: {}",
-        clone_path.display()
-    );
-    Ok(())
+    let list = || -> Result<Vec<String>> {
+        let repo = gix::init_bare(&scratch)?;
+        let remote = repo.remote_at(url)?.with_refspecs(
+            ["+refs/heads/*:refs/heads/*", "+refs/tags/*:refs/tags/*"],
+            gix::remote::Direction::Fetch,
+        )?;
+        let (ref_map, _) = remote
+            .connect(gix::remote::Direction::Fetch)?
+            .ref_map(gix::progress::Discard, Default::default())?;
+        Ok(ref_map
+            .remote_refs
+            .iter()
+            .filter_map(|remote_ref| {
+                let name = remote_ref.unpack().0.to_str().ok()?;
+                name.strip_prefix("refs/heads/")
+                    .or_else(|| name.strip_prefix("refs/tags/"))
+                    .map(str::to_string)
+            })
+            .collect())
+    };
+    let refs = list();
+    let _ = fs::remove_dir_all(&scratch);
+    refs
 }
 
-pub fn execute_dump(dump_file: &str, config: &Config) -> Result<()> {
-    let content = fs::read_to_string(dump_file).context("Failed to read dump file")?;
+/// Expand an alias prefix such as `gh:` in `gh:owner/repo`.
+fn expand_alias(url: &str, aliases: &BTreeMap<String, String>) -> String {
+    if let Some((alias, rest)) = url.trim().split_once(':')
+        && !rest.starts_with("//")
+        && let Some(target) = aliases.get(alias)
+    {
+        let separator = if target.ends_with(['/', ':']) { "" } else { "/" };
+        return format!("{}{}{}", target, separator, rest);
+    }
 
-    for line in content.lines() {
-        let line = line.trim();
-        if line.is_empty() || line.starts_with('#') {
-            continue;
-        }
+    url.to_string()
+}
 
-        let mut parts = line.split_whitespace();
-        let Some(url) = parts.next() else { continue };
-        
-        let config_clone = Config {
-            base_dir: config.base_dir.clone(),
-            branch: parts.next().map(|s| s.to_string()).or_else(|| config.branch.clone()),
-            default_host: config.default_host.clone(),
-            default_scheme: config.default_scheme.clone(),
-            skip_host: config.skip_host,
-        };
+/// Apply git's `url.<base>.insteadOf` rules. As in git, the longest matching prefix wins.
+fn rewrite_url(url: &str, rewrites: &[(String, String)]) -> String {
+    rewrites
+        .iter()
+        .filter(|(prefix, _)| url.starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(prefix, base)| format!("{}{}", base, &url[prefix.len()..]))
+        .unwrap_or_else(|| url.to_string())
+}
+
+/// Check that the repository already at `clone_path` is `repo_info`, updating it if requested.
+fn use_existing(repo_info: &RepoInfo, clone_path: &Path, config: &Config) -> Result<Option<String>> {
+    verify_origin(repo_info, clone_path)?;
+
+    if !config.update {
+        return Ok(None);
+    }
+    sync::update_repository(clone_path, config.fetch_timeout)
+        .map(Some)
+        .with_context(|| format!("Failed to update {}", clone_path.display()))
+}
 
-        match execute(url, &config_clone) {
-            Ok(_) => println!("✓ Cloned {}", url),
-            Err(e) => eprintln!("✗ Failed to clone {}: {}", url, e),
+fn verify_origin(repo_info: &RepoInfo, clone_path: &Path) -> Result<()> {
+    let repo = gix::open(clone_path)
+        .with_context(|| format!("{} exists but is not a git repository", clone_path.display()))?;
+    let origin = repo
+        .find_remote("origin")
+        .ok()
+        .and_then(|remote| remote.url(gix::remote::Direction::Fetch).map(|url| url.to_bstring().to_string()));
+
+    match origin {
+        Some(url) if same_repository(&url, repo_info) => Ok(()),
+        actual => Err(OriginMismatch {
+            path: clone_path.to_path_buf(),
+            expected: repo_info.full_url.clone(),
+            actual,
         }
+        .into()),
     }
+}
 
+/// Whether `url` points at the repository described by `repo_info`, ignoring the transport.
+fn same_repository(url: &str, repo_info: &RepoInfo) -> bool {
+    url == repo_info.full_url
+        || parse_repo_url(url).is_ok_and(|origin| {
+            origin.host == repo_info.host && origin.namespace == repo_info.namespace && origin.name == repo_info.name
+        })
+}
+
+fn clone_into(
+    repo_info: &RepoInfo,
+    clone_path: &Path,
+    branch: &Option<String>,
+    options: &CloneOptions,
+) -> Result<()> {
+    if let Some(parent) = clone_path.parent() {
+        fs::create_dir_all(parent).context("Failed to create parent directories")?;
+    }
+
+    clone_repository(&repo_info.full_url, clone_path, branch, options)?;
     Ok(())
 }
 
@@ -85,7 +534,8 @@ fn get_clone_path(repo_info: &RepoInfo, base_dir: &str, skip_host: bool) -> Path
         path.push(&repo_info.host);
     }
 
-    path.extend([&repo_info.owner, &repo_info.name]);
+    path.push(repo_info.namespace_path());
+    path.push(&repo_info.name);
     path
 }
 
@@ -93,31 +543,823 @@ fn build_url(repo_info: &RepoInfo, scheme: &str) -> String {
     match scheme {
         "ssh" => format!(
             "git@{}:{}/{}.git",
-            repo_info.host, repo_info.owner, repo_info.name
+            repo_info.host, repo_info.namespace, repo_info.name
         ),
         _ => format!(
             "https://{}/{}/{}.git",
-            repo_info.host, repo_info.owner, repo_info.name
+            repo_info.host, repo_info.namespace, repo_info.name
         ),
     }
 }
 
-fn clone_repository(url: &str, path: &Path, branch: &Option<String>) -> Result<()> {
-    let mut cmd = Command::new("git");
-    cmd.arg("clone");
+/// The clone path is already taken by a checkout of a different repository.
+#[derive(Debug)]
+pub struct OriginMismatch {
+    pub path: PathBuf,
+    pub expected: String,
+    /// The existing checkout's origin URL, or `None` if it has no origin remote.
+    pub actual: Option<String>,
+}
+
+impl fmt::Display for OriginMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.actual {
+            Some(actual) => write!(
+                f,
+                "Repository at {} has origin {}, expected {}",
+                self.path.display(),
+                actual,
+                self.expected
+            ),
+            None => write!(
+                f,
+                "Repository at {} has no origin remote, expected {}",
+                self.path.display(),
+                self.expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for OriginMismatch {}
+
+/// Errors produced while cloning a repository in-process.
+#[derive(Debug)]
+pub enum CloneError {
+    /// The destination could not be initialized or the URL was rejected.
+    Prepare(Box<gix::clone::Error>),
+    /// The requested branch or tag is not a valid reference name.
+    InvalidRef(gix::validate::reference::name::Error),
+    /// Connecting to the remote or receiving the pack failed.
+    Fetch(Box<gix::clone::fetch::Error>),
+    /// Writing the working tree failed.
+    Checkout(Box<gix::clone::checkout::main_worktree::Error>),
+    /// Writing a sparse working tree or its sparse-checkout configuration failed.
+    Sparse(Box<dyn std::error::Error + Send + Sync>),
+    /// The requested commit is not in the cloned history or couldn't be checked out.
+    Commit(Box<dyn std::error::Error + Send + Sync>),
+    /// Cloning or checking out a submodule failed.
+    Submodules(Box<dyn std::error::Error + Send + Sync>),
+    /// The `git` executable could not be run.
+    Spawn(std::io::Error),
+    /// `git` ran but reported an error, carrying its output.
+    Git(String),
+}
+
+impl fmt::Display for CloneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CloneError::Prepare(_) => write!(f, "Failed to prepare clone destination"),
+            CloneError::InvalidRef(_) => write!(f, "Invalid branch or tag name"),
+            CloneError::Fetch(_) => write!(f, "Failed to fetch from remote"),
+            CloneError::Checkout(_) => write!(f, "Failed to check out working tree"),
+            CloneError::Sparse(_) => write!(f, "Failed to check out sparse working tree"),
+            CloneError::Commit(_) => write!(f, "Failed to check out commit"),
+            CloneError::Submodules(_) => write!(f, "Failed to clone submodules"),
+            CloneError::Spawn(e) if e.kind() == io::ErrorKind::NotFound => {
+                write!(f, "Partial clones need the git executable, which was not found")
+            }
+            CloneError::Spawn(_) => write!(f, "Failed to run git"),
+            CloneError::Git(output) => write!(f, "git failed: {}", output),
+        }
+    }
+}
+
+impl std::error::Error for CloneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CloneError::Prepare(e) => Some(e.as_ref()),
+            CloneError::InvalidRef(e) => Some(e),
+            CloneError::Fetch(e) => Some(e.as_ref()),
+            CloneError::Checkout(e) => Some(e.as_ref()),
+            CloneError::Sparse(e) => Some(e.as_ref()),
+            CloneError::Commit(e) => Some(e.as_ref()),
+            CloneError::Submodules(e) => Some(e.as_ref()),
+            CloneError::Spawn(e) => Some(e),
+            CloneError::Git(_) => None,
+        }
+    }
+}
+
+/// Clone `url` into `path` and check out `branch`, which may also be a tag or a full commit id.
+/// A commit can't be fetched by name, so the remote `HEAD` is cloned and the commit checked out
+/// afterwards with a detached `HEAD`. A clone that fails part-way, e.g. while cloning its
+/// submodules, is removed again so that a later run doesn't take it for a complete one.
+fn clone_repository(
+    url: &str,
+    path: &Path,
+    branch: &Option<String>,
+    options: &CloneOptions,
+) -> Result<(), CloneError> {
+    let existed = path.exists();
+    let commit = branch.as_deref().and_then(|branch| gix::ObjectId::from_hex(branch.as_bytes()).ok());
+    let branch = if commit.is_some() { &None } else { branch };
+    let result = if options.filter.is_some() {
+        clone_with_git(url, path, branch, commit, options)
+    } else {
+        clone_with_gix(url, path, branch, commit, options)
+    };
+    if result.is_err() && !existed {
+        let _ = fs::remove_dir_all(path);
+    }
+    result
+}
+
+fn clone_with_gix(
+    url: &str,
+    path: &Path,
+    branch: &Option<String>,
+    commit: Option<gix::ObjectId>,
+    options: &CloneOptions,
+) -> Result<(), CloneError> {
+    let mut prepare = gix::prepare_clone(url, path).map_err(|e| CloneError::Prepare(Box::new(e)))?;
 
     if let Some(branch) = branch {
-        cmd.arg("-b").arg(branch);
+        prepare = prepare
+            .with_ref_name(Some(branch.as_str()))
+            .map_err(CloneError::InvalidRef)?;
     }
 
-    cmd.arg(url).arg(path);
+    if options.single_branch {
+        let branch = branch.clone();
+        prepare = prepare.configure_remote(move |mut remote| {
+            let branch = match &branch {
+                Some(branch) => Some(branch.clone()),
+                None => remote_head_branch(&remote)?,
+            };
+            if let Some(branch) = branch {
+                let refspec = format!("+refs/heads/{0}:refs/remotes/origin/{0}", branch);
+                remote.replace_refspecs([refspec.as_str()], gix::remote::Direction::Fetch)?;
+            }
+            Ok(remote)
+        });
+    }
+
+    if let Some(depth) = options.depth {
+        prepare = prepare.with_shallow(gix::remote::fetch::Shallow::DepthAtRemote(depth));
+    }
 
-    let output = cmd.output().context("Failed to execute git clone")?;
+    let (mut checkout, _) = prepare
+        .fetch_then_checkout(gix::progress::Discard, &gix::interrupt::IS_INTERRUPTED)
+        .map_err(|e| CloneError::Fetch(Box::new(e)))?;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(anyhow::anyhow!("Git clone failed: {}", stderr));
+    if options.sparse.is_empty() {
+        checkout
+            .main_worktree(gix::progress::Discard, &gix::interrupt::IS_INTERRUPTED)
+            .map_err(|e| CloneError::Checkout(Box::new(e)))?;
+    } else {
+        let repo = checkout.persist();
+        sparse_checkout(&repo, &options.sparse).map_err(|e| CloneError::Sparse(e.into()))?;
+    }
+
+    if let Some(commit) = commit {
+        pin_commit(path, commit, true).map_err(|e| CloneError::Commit(e.into()))?;
+    }
+
+    if options.submodules {
+        update_submodules(path).map_err(|e| CloneError::Submodules(e.into()))?;
+    }
+
+    Ok(())
+}
+
+/// The branch the remote's `HEAD` points at, or `None` if it doesn't point at a branch.
+fn remote_head_branch(remote: &gix::Remote<'_>) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+    let head = gix::refspec::parse("HEAD".into(), gix::refspec::parse::Operation::Fetch)?.to_owned();
+    let options = gix::remote::ref_map::Options {
+        extra_refspecs: vec![head],
+        ..Default::default()
+    };
+    let (ref_map, _) = remote
+        .connect(gix::remote::Direction::Fetch)?
+        .ref_map(gix::progress::Discard, options)?;
+
+    Ok(ref_map.remote_refs.iter().find_map(|remote_ref| match remote_ref {
+        gix::protocol::handshake::Ref::Symbolic { full_ref_name, target, .. }
+        | gix::protocol::handshake::Ref::Unborn { full_ref_name, target } if full_ref_name == "HEAD" => {
+            target.to_str().ok()?.strip_prefix("refs/heads/").map(str::to_string)
+        }
+        _ => None,
+    }))
+}
+
+/// Clone with the `git` executable, for partial clones which gix can't request yet.
+fn clone_with_git(
+    url: &str,
+    path: &Path,
+    branch: &Option<String>,
+    commit: Option<gix::ObjectId>,
+    options: &CloneOptions,
+) -> Result<(), CloneError> {
+    let mut clone = Command::new("git");
+    clone.args(["clone", "--quiet"]);
+    if let Some(depth) = options.depth {
+        clone.arg(format!("--depth={}", depth));
+    }
+    if let Some(filter) = &options.filter {
+        clone.arg(format!("--filter={}", filter));
+    }
+    if options.single_branch {
+        clone.arg("--single-branch");
+    }
+    if let Some(branch) = branch {
+        clone.args(["--branch", branch]);
+    }
+    if !options.sparse.is_empty() {
+        clone.arg("--sparse");
+    }
+    if options.submodules && commit.is_none() {
+        clone.arg("--recurse-submodules");
+    }
+    run_git(clone.arg("--").arg(url).arg(path))?;
+
+    if !options.sparse.is_empty() {
+        let mut sparse = Command::new("git");
+        sparse.arg("-C").arg(path).args(["sparse-checkout", "set", "--cone", "--"]);
+        run_git(sparse.args(&options.sparse))?;
+    }
+
+    if let Some(commit) = commit {
+        let mut checkout = Command::new("git");
+        checkout.arg("-C").arg(path).args(["checkout", "--quiet", "--detach"]);
+        run_git(checkout.arg(commit.to_string()))?;
+        if options.submodules {
+            let mut submodules = Command::new("git");
+            submodules.arg("-C").arg(path).args(["submodule", "update", "--quiet", "--init", "--recursive"]);
+            run_git(&mut submodules)?;
+        }
     }
 
     Ok(())
 }
+
+/// Clone the submodules of the repository at `path` and check out the commits recorded for
+/// them, recursively. Like `git submodule update --init`, submodules that are already cloned
+/// are left alone. Each submodule keeps its git directory in its own `.git`.
+fn update_submodules(path: &Path) -> Result<()> {
+    let repo = gix::open(path)?;
+    let Some(submodules) = repo.submodules()? else {
+        return Ok(());
+    };
+
+    for submodule in submodules {
+        let name = submodule.name().to_owned();
+        let update = || -> Result<()> {
+            let Some(commit) = submodule.index_id()? else {
+                return Ok(());
+            };
+            if submodule.state()?.repository_exists {
+                return Ok(());
+            }
+
+            let url = submodule_url(&repo, submodule.url()?.to_bstring().to_str()?)?;
+            edit_local_config(&repo, |config| {
+                config.set_raw_value_by("submodule", Some(name.as_bstr()), "url", url.as_str())?;
+                Ok(())
+            })?;
+
+            let work_dir = submodule.work_dir()?;
+            let (mut checkout, _) = gix::prepare_clone(url.as_str(), &work_dir)?
+                .fetch_then_checkout(gix::progress::Discard, &gix::interrupt::IS_INTERRUPTED)?;
+            let (mut cloned, _) = checkout.main_worktree(gix::progress::Discard, &gix::interrupt::IS_INTERRUPTED)?;
+
+            if cloned.head_id().ok().map(|id| id.detach()) != Some(commit) {
+                cloned.committer_or_set_generic_fallback()?;
+                cloned
+                    .find_commit(commit)
+                    .with_context(|| format!("Commit {} is not on a branch of {}", commit, url))?;
+                worktree::update_worktree(&cloned, commit)?;
+                cloned.reference(
+                    "HEAD",
+                    commit,
+                    gix::refs::transaction::PreviousValue::Any,
+                    "get: check out submodule commit",
+                )?;
+            }
+
+            update_submodules(&work_dir)
+        };
+        update().with_context(|| format!("Failed to update submodule {}", name))?;
+    }
+    Ok(())
+}
+
+/// Resolve a submodule URL relative to the superproject's `origin`, as git does for URLs that
+/// start with `./` or `../`.
+fn submodule_url(repo: &gix::Repository, url: &str) -> Result<String> {
+    if !url.starts_with("./") && !url.starts_with("../") {
+        return Ok(url.to_string());
+    }
+
+    let origin = repo
+        .find_remote("origin")?
+        .url(gix::remote::Direction::Fetch)
+        .context("The superproject has no origin URL")?
+        .to_bstring()
+        .to_string();
+    let mut base = origin.trim_end_matches('/').to_string();
+    let mut rest = url;
+    loop {
+        if let Some(next) = rest.strip_prefix("./") {
+            rest = next;
+        } else if let Some(next) = rest.strip_prefix("../") {
+            let end = base.rfind(['/', ':']).context("Relative submodule URL leaves the origin URL")?;
+            base.truncate(end);
+            rest = next;
+        } else {
+            break;
+        }
+    }
+    Ok(format!("{}/{}", base, rest))
+}
+
+fn run_git(command: &mut Command) -> Result<(), CloneError> {
+    let output = command.output().map_err(CloneError::Spawn)?;
+    if !output.status.success() {
+        return Err(CloneError::Git(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    Ok(())
+}
+
+/// Check out only `dirs` of the freshly cloned `repo`, in the same way as git's cone mode:
+/// files at the top level and directly inside each parent of a listed directory are kept too.
+/// Everything else is marked skip-worktree, and the patterns are recorded so git honors them.
+fn sparse_checkout(repo: &gix::Repository, dirs: &[String]) -> Result<()> {
+    let workdir = repo.workdir().context("Repository has no working tree")?;
+    let Ok(tree_id) = repo.head_tree_id() else {
+        // Nothing to check out from an empty remote
+        return Ok(());
+    };
+
+    let mut index = repo.index_from_tree(&tree_id)?;
+    for (entry, path) in index.entries_mut_with_paths() {
+        if !worktree::in_sparse_cone(path, dirs) {
+            entry
+                .flags
+                .insert(gix::index::entry::Flags::SKIP_WORKTREE | gix::index::entry::Flags::EXTENDED);
+        }
+    }
+
+    let mut options = repo.checkout_options(gix::worktree::stack::state::attributes::Source::IdMapping)?;
+    options.destination_is_initially_empty = true;
+    gix::worktree::state::checkout(
+        &mut index,
+        workdir,
+        repo.objects.clone().into_arc()?,
+        &gix::progress::Discard,
+        &gix::progress::Discard,
+        &gix::interrupt::IS_INTERRUPTED,
+        options,
+    )?;
+    index.write(Default::default())?;
+
+    let info_dir = repo.git_dir().join("info");
+    fs::create_dir_all(&info_dir)?;
+    fs::write(info_dir.join("sparse-checkout"), worktree::sparse_cone_patterns(dirs))?;
+
+    edit_local_config(repo, |config| {
+        config.set_raw_value_by("core", None, "sparseCheckout", "true")?;
+        config.set_raw_value_by("core", None, "sparseCheckoutCone", "true")?;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{git, init_source_repo, temp_dir, write_tree};
+    use std::num::NonZeroU32;
+
+    fn test_config(base_dir: &str) -> Config {
+        Config {
+            base_dir: base_dir.to_string(),
+            branch: None,
+            default_host: "github.com".to_string(),
+            default_scheme: "https".to_string(),
+            skip_host: false,
+            jobs: 1,
+            aliases: BTreeMap::new(),
+            url_rewrites: Vec::new(),
+            update: false,
+            fetch_timeout: Duration::from_secs(30),
+            clone_options: CloneOptions::default(),
+        }
+    }
+
+    #[test]
+    fn test_resolve_repo_alias_and_rewrite() {
+        let mut config = test_config("/base");
+        config.aliases = BTreeMap::from([
+            ("gh".to_string(), "github.com".to_string()),
+            ("work".to_string(), "git@git.corp.example.com:".to_string()),
+        ]);
+        config.url_rewrites = vec![
+            ("https://".to_string(), "unused://".to_string()),
+            ("https://github.com/".to_string(), "https://mirror.example.com/gh/".to_string()),
+        ];
+
+        let (info, path) = resolve_repo("gh:owner/repo", &config).unwrap();
+        assert_eq!(info.full_url, "https://mirror.example.com/gh/owner/repo.git");
+        assert_eq!(path, PathBuf::from("/base/github.com/owner/repo"));
+
+        let (info, path) = resolve_repo("work:team/service", &config).unwrap();
+        assert_eq!(info.full_url, "git@git.corp.example.com:team/service.git");
+        assert_eq!(path, PathBuf::from("/base/git.corp.example.com/team/service"));
+    }
+
+    #[test]
+    fn test_clone_local_repository() {
+        let dir = temp_dir("clone-local");
+        let source = dir.join("source");
+        let commit = init_source_repo(&source);
+
+        let dest = dir.join("dest");
+        clone_repository(source.to_str().unwrap(), &dest, &None, &CloneOptions::default()).unwrap();
+
+        assert_eq!(fs::read_to_string(dest.join("README")).unwrap(), "hello\n");
+        let cloned = gix::open(&dest).unwrap();
+        assert_eq!(cloned.head_id().unwrap().detach(), commit);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_clone_local_repository_branch() {
+        let dir = temp_dir("clone-branch");
+        let source = dir.join("source");
+        init_source_repo(&source);
+
+        let dest = dir.join("dest");
+        clone_repository(
+            source.to_str().unwrap(),
+            &dest,
+            &Some("feature".to_string()),
+            &CloneOptions::default(),
+        ).unwrap();
+
+        let cloned = gix::open(&dest).unwrap();
+        let head = cloned.head_name().unwrap().unwrap();
+        assert_eq!(head.as_bstr(), "refs/heads/feature");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_clone_local_repository_commit() {
+        let dir = temp_dir("clone-commit");
+        let source = dir.join("source");
+        let first = init_source_repo(&source);
+        let repo = gix::open(&source).unwrap();
+        let tree = repo.head_tree_id().unwrap().detach();
+        repo.commit("HEAD", "second", tree, [first]).unwrap();
+
+        let dest = dir.join("dest");
+        clone_repository(source.to_str().unwrap(), &dest, &Some(first.to_string()), &CloneOptions::default())
+            .unwrap();
+
+        let cloned = gix::open(&dest).unwrap();
+        assert_eq!(cloned.head_id().unwrap().detach(), first);
+        assert!(cloned.head_name().unwrap().is_none());
+        assert_eq!(fs::read_to_string(dest.join("README")).unwrap(), "hello\n");
+
+        let missing = Some("0123456789abcdef0123456789abcdef01234567".to_string());
+        let other = dir.join("other");
+        let err = clone_repository(source.to_str().unwrap(), &other, &missing, &CloneOptions::default()).unwrap_err();
+        assert!(matches!(err, CloneError::Commit(_)));
+        assert!(!other.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_web_branch() {
+        let dir = temp_dir("web-branch");
+        let source = dir.join("source");
+        let commit = init_source_repo(&source);
+        let repo = gix::open(&source).unwrap();
+        for name in ["refs/heads/release/v1", "refs/tags/v1.0"] {
+            repo.reference(name, commit, gix::refs::transaction::PreviousValue::Any, "create")
+                .unwrap();
+        }
+        let url = source.to_str().unwrap();
+
+        assert_eq!(resolve_web_branch(url, "feature/docs").unwrap(), "feature");
+        assert_eq!(resolve_web_branch(url, "release/v1/docs/guide.md").unwrap(), "release/v1");
+        assert_eq!(resolve_web_branch(url, "v1.0/README").unwrap(), "v1.0");
+        assert_eq!(resolve_web_branch(url, "missing/docs").unwrap(), "missing/docs");
+
+        let repo_info = RepoInfo {
+            full_url: url.to_string(),
+            branch: Some("feature/docs".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(checkout_ref(None, &repo_info).unwrap().as_deref(), Some("feature"));
+        assert_eq!(checkout_ref(Some("main".to_string()), &repo_info).unwrap().as_deref(), Some("main"));
+        let permalink = RepoInfo {
+            commit: Some(commit.to_string()),
+            ..repo_info
+        };
+        assert_eq!(checkout_ref(None, &permalink).unwrap(), Some(commit.to_string()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_clone_single_branch_follows_remote_head() {
+        let dir = temp_dir("clone-single-branch");
+        let source = dir.join("source");
+        init_source_repo(&source);
+        let head = gix::open(&source).unwrap().head_name().unwrap().unwrap();
+        let branch = head.shorten().to_string();
+
+        let dest = dir.join("dest");
+        let options = CloneOptions {
+            single_branch: true,
+            ..Default::default()
+        };
+        clone_repository(source.to_str().unwrap(), &dest, &None, &options).unwrap();
+
+        let cloned = gix::open(&dest).unwrap();
+        assert_eq!(cloned.head_name().unwrap().unwrap(), head);
+        assert!(cloned.find_reference(format!("refs/remotes/origin/{}", branch).as_str()).is_ok());
+        assert!(cloned.find_reference("refs/remotes/origin/feature").is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_clone_submodules() {
+        let dir = temp_dir("clone-submodules");
+        let (source, sub) = (dir.join("source"), dir.join("sub"));
+        init_source_repo(&source);
+        let recorded = init_source_repo(&sub);
+        git(&source, &["submodule", "add", "--quiet", "../sub", "lib"]);
+        git(&source, &["commit", "--quiet", "-m", "add lib"]);
+        git(&sub, &["commit", "--quiet", "--allow-empty", "-m", "after"]);
+
+        let dest = dir.join("dest");
+        let options = CloneOptions {
+            submodules: true,
+            ..Default::default()
+        };
+
+        // A clone whose submodule can't be fetched is removed rather than left half-done
+        fs::rename(&sub, dir.join("moved")).unwrap();
+        let err = clone_repository(source.to_str().unwrap(), &dest, &None, &options).unwrap_err();
+        assert!(matches!(err, CloneError::Submodules(_)));
+        assert!(!dest.exists());
+        fs::rename(dir.join("moved"), &sub).unwrap();
+
+        clone_repository(source.to_str().unwrap(), &dest, &None, &options).unwrap();
+        assert_eq!(fs::read_to_string(dest.join("lib/README")).unwrap(), "hello\n");
+        let submodule = gix::open(dest.join("lib")).unwrap();
+        assert_eq!(submodule.head_id().unwrap().detach(), recorded);
+        assert!(submodule.head_name().unwrap().is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_clone_shallow_sparse() {
+        let dir = temp_dir("clone-sparse");
+        let source = dir.join("source");
+        let first = init_source_repo(&source);
+        let repo = gix::open(&source).unwrap();
+        let root = write_tree(
+            &repo,
+            &[
+                ("README", "hello\n"),
+                ("docs/guide.md", "guide"),
+                ("src/app/main.rs", "main"),
+                ("src/lib.rs", "lib"),
+                ("tests/it.rs", "it"),
+            ],
+        );
+        repo.commit("HEAD", "add files", root, [first]).unwrap();
+
+        let dest = dir.join("dest");
+        let options = CloneOptions {
+            depth: NonZeroU32::new(1),
+            sparse: vec!["docs".to_string(), "src/app".to_string()],
+            ..Default::default()
+        };
+        clone_repository(&format!("file://{}", source.display()), &dest, &None, &options).unwrap();
+
+        assert!(dest.join("README").exists());
+        assert!(dest.join("docs/guide.md").exists());
+        assert!(dest.join("src/app/main.rs").exists());
+        assert!(dest.join("src/lib.rs").exists());
+        assert!(!dest.join("tests").exists());
+        assert_eq!(
+            fs::read_to_string(dest.join(".git/info/sparse-checkout")).unwrap(),
+            "/*\n!/*/\n/docs/\n/src/\n!/src/*/\n/src/app/\n"
+        );
+        assert!(dest.join(".git/shallow").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_execute_dump_restores_state() {
+        let dir = temp_dir("dump-restore");
+        let source = dir.join("source");
+        let first = init_source_repo(&source);
+        let repo = gix::open(&source).unwrap();
+        let blob = repo.write_blob(b"second\n").unwrap().detach();
+        let tree = gix::objs::Tree {
+            entries: vec![gix::objs::tree::Entry {
+                mode: gix::objs::tree::EntryKind::Blob.into(),
+                filename: "README".into(),
+                oid: blob,
+            }],
+        };
+        let tree = repo.write_object(&tree).unwrap().detach();
+        repo.commit("HEAD", "second", tree, [first]).unwrap();
+
+        let root = dir.join("root");
+        let dump_file = dir.join("dump.toml");
+        let entry = dump::Entry {
+            url: source.to_str().unwrap().to_string(),
+            path: Some("team/app".to_string()),
+            commit: Some(first.to_string()),
+            remotes: BTreeMap::from([("upstream".to_string(), "https://example.com/app.git".to_string())]),
+            ..Default::default()
+        };
+        fs::write(&dump_file, dump::to_string(&[entry]).unwrap()).unwrap();
+
+        execute_dump(dump_file.to_str().unwrap(), &test_config(root.to_str().unwrap())).unwrap();
+
+        let clone_path = root.join("team/app");
+        let cloned = gix::open(&clone_path).unwrap();
+        assert_eq!(cloned.head_id().unwrap().detach(), first);
+        assert_eq!(fs::read_to_string(clone_path.join("README")).unwrap(), "hello\n");
+        let upstream = cloned.find_remote("upstream").unwrap();
+        assert_eq!(
+            upstream.url(gix::remote::Direction::Fetch).unwrap().to_bstring(),
+            "https://example.com/app.git"
+        );
+
+        // A clone whose commit can't be checked out doesn't stay behind
+        let entry = dump::Entry {
+            url: source.to_str().unwrap().to_string(),
+            path: Some("team/missing".to_string()),
+            commit: Some("1".repeat(40)),
+            ..Default::default()
+        };
+        fs::write(&dump_file, dump::to_string(&[entry]).unwrap()).unwrap();
+        assert!(execute_dump(dump_file.to_str().unwrap(), &test_config(root.to_str().unwrap())).is_err());
+        assert!(!root.join("team/missing").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_execute_dump_pins_shallow_sparse_clone() {
+        let dir = temp_dir("dump-pin-sparse");
+        let source = dir.join("source");
+        let mut parent = init_source_repo(&source);
+        let repo = gix::open(&source).unwrap();
+        let commit = |parent: gix::ObjectId, guide: &str, other: &str| {
+            let tree = write_tree(&repo, &[("README", "hello\n"), ("docs/guide.md", guide), (other, other)]);
+            repo.commit("HEAD", "update", tree, [parent]).unwrap().detach()
+        };
+        let pinned = commit(parent, "v1", "tests/old.rs");
+        parent = commit(pinned, "v2", "src/new.rs");
+        commit(parent, "v3", "src/newer.rs");
+
+        let root = dir.join("root");
+        let dump_file = dir.join("dump.toml");
+        let entry = dump::Entry {
+            url: format!("file://{}", source.display()),
+            path: Some("app".to_string()),
+            commit: Some(pinned.to_string()),
+            depth: NonZeroU32::new(1),
+            sparse: vec!["docs".to_string()],
+            ..Default::default()
+        };
+        fs::write(&dump_file, dump::to_string(&[entry]).unwrap()).unwrap();
+
+        execute_dump(dump_file.to_str().unwrap(), &test_config(root.to_str().unwrap())).unwrap();
+
+        let clone_path = root.join("app");
+        let cloned = gix::open(&clone_path).unwrap();
+        assert_eq!(cloned.head_id().unwrap().detach(), pinned);
+        assert_eq!(fs::read_to_string(clone_path.join("docs/guide.md")).unwrap(), "v1");
+        assert!(!clone_path.join("tests").exists());
+        assert!(!clone_path.join("src").exists());
+        let index = cloned.open_index().unwrap();
+        let entry = index.entry_by_path("tests/old.rs".into()).unwrap();
+        assert!(entry.flags.contains(gix::index::entry::Flags::SKIP_WORKTREE));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_execute_dump_clone_path_conflicts() {
+        let dir = temp_dir("dump-conflicts");
+        let (source, other) = (dir.join("source"), dir.join("other"));
+        init_source_repo(&source);
+        init_source_repo(&other);
+
+        let root = dir.join("root");
+        let dump_file = dir.join("dump.txt");
+        let entry = |url: &Path| dump::Entry {
+            url: url.to_str().unwrap().to_string(),
+            path: Some("app".to_string()),
+            ..Default::default()
+        };
+        let entries = [entry(&source), entry(&source), entry(&other)];
+        fs::write(&dump_file, dump::to_string(&entries).unwrap()).unwrap();
+
+        let mut config = test_config(root.to_str().unwrap());
+        config.jobs = 4;
+        let err = execute_dump(dump_file.to_str().unwrap(), &config).unwrap_err();
+        assert_eq!(err.to_string(), "1 of 2 repositories failed to clone");
+
+        let cloned = gix::open(root.join("app")).unwrap();
+        let origin = cloned.find_remote("origin").unwrap();
+        assert_eq!(
+            origin.url(gix::remote::Direction::Fetch).unwrap().path,
+            source.to_str().unwrap()
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_isolated_panic_fails_entry() {
+        let outcome = isolated(|| panic!("no object-id in refspec"));
+        let DumpOutcome::Failed(error) = outcome else {
+            panic!("a panic should fail the entry");
+        };
+        assert_eq!(error.to_string(), "Cloning panicked: no object-id in refspec");
+        assert!(matches!(isolated(|| DumpOutcome::Cloned(PathBuf::new())), DumpOutcome::Cloned(_)));
+    }
+
+    #[test]
+    fn test_read_dump_gzip_file_url() {
+        use std::io::Write;
+
+        let dir = temp_dir("dump-gzip");
+        let content = "github.com/a/b\ngithub.com/c/d dev\n";
+        let path = dir.join("dump.gz");
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(content.as_bytes()).unwrap();
+        fs::write(&path, encoder.finish().unwrap()).unwrap();
+
+        assert_eq!(read_dump(path.to_str().unwrap()).unwrap(), content);
+        assert_eq!(read_dump(&format!("file://{}", path.display())).unwrap(), content);
+        assert_eq!(read_dump(&format!("file://localhost{}", path.display())).unwrap(), content);
+        let err = read_dump(&format!("file://otherhost{}", path.display())).unwrap_err();
+        assert_eq!(err.to_string(), format!("Dump URL file://otherhost{} does not name a local file", path.display()));
+        assert!(read_dump(dir.join("missing").to_str().unwrap()).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_verify_origin() {
+        let dir = temp_dir("verify-origin");
+        let source = dir.join("source");
+        init_source_repo(&source);
+        let dest = dir.join("dest");
+        clone_repository(source.to_str().unwrap(), &dest, &None, &CloneOptions::default()).unwrap();
+
+        let cloned = gix::open(&dest).unwrap();
+        let url = cloned
+            .find_remote("origin")
+            .unwrap().url(gix::remote::Direction::Fetch).unwrap().to_bstring().to_string();
+        let matching = RepoInfo {
+            full_url: url,
+            ..Default::default()
+        };
+        verify_origin(&matching, &dest).unwrap();
+
+        let other = parse_repo_url("https://github.com/owner/other").unwrap();
+        let err = verify_origin(&other, &dest).unwrap_err();
+        assert!(err.downcast_ref::<OriginMismatch>().is_some());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_clone_missing_branch_fails() {
+        let dir = temp_dir("clone-missing");
+        let source = dir.join("source");
+        init_source_repo(&source);
+
+        let dest = dir.join("dest");
+        let err = clone_repository(
+            source.to_str().unwrap(),
+            &dest,
+            &Some("nope".to_string()),
+            &CloneOptions::default(),
+        )
+        .unwrap_err();
+
+        assert!(matches!(err, CloneError::Fetch(_)));
+        assert!(!dest.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,91 +1,562 @@
-use anyhow::Result;
+use crate::cache::{self, Stamp};
+use crate::{dump, normalize_path, worktree};
+use anyhow::{Context, Result};
 use gix::bstr::ByteSlice;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::io::Write;
+use std::io::{IsTerminal, Write};
+use std::num::NonZeroU32;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+use std::time::Duration;
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 
-#[derive(Debug, Clone)]
-struct RepoStatus {
-    path: PathBuf,
-    current_branch: String,
-    all_branches: Vec<BranchInfo>,
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepoStatus {
+    pub path: PathBuf,
+    pub kind: RepoKind,
+    pub current_branch: String,
+    /// The local branches, the checked-out one first.
+    #[serde(rename = "branches")]
+    pub all_branches: Vec<BranchInfo>,
+    /// Uncommitted changes in the index and working tree.
+    pub changes: ChangeCounts,
+    pub remotes: Vec<RemoteInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fetch_error: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub submodules: Vec<SubmoduleStatus>,
+    /// Why the submodules couldn't be listed, e.g. because `.gitmodules` is malformed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub submodule_error: Option<String>,
 }
 
-#[derive(Debug, Clone)]
-struct BranchInfo {
-    name: String,
-    status: BranchStatus,
+/// What kind of checkout a discovered repository is.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RepoKind {
+    /// A repository with its own working tree.
+    Main,
+    /// A linked worktree of the repository at `main` (its working tree, or git dir if bare).
+    Worktree { main: PathBuf },
+    /// A submodule checkout whose git dir lives inside the superproject's.
+    Submodule,
+    /// A repository without a working tree.
+    Bare,
+}
+
+/// A submodule registered in a repository's `.gitmodules`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubmoduleStatus {
+    /// Location relative to the superproject's working tree.
+    pub path: PathBuf,
+    /// Whether the submodule is cloned and checked out.
+    pub initialized: bool,
+    /// Whether the checked-out commit differs from the one recorded by the superproject.
+    pub out_of_date: bool,
+    /// Whether the submodule's working tree has uncommitted or untracked changes.
+    pub dirty: bool,
+}
+
+impl SubmoduleStatus {
+    fn is_ok(&self) -> bool {
+        self.initialized && !self.out_of_date && !self.dirty
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BranchInfo {
+    pub name: String,
+    pub status: BranchStatus,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteInfo {
+    pub name: String,
+    pub url: Option<String>,
 }
 
-#[derive(Debug, Clone)]
-enum BranchStatus {
+/// How a branch relates to its upstream, serialized with the variant name in `state`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum BranchStatus {
     Ok,
-    Ahead(usize),
-    Behind(usize),
+    Ahead { ahead: usize },
+    Behind { behind: usize },
     Diverged { ahead: usize, behind: usize },
     NoUpstream,
-    Uncommitted { count: usize },
-    Untracked { count: usize },
+    UpstreamGone,
 }
 
-pub fn execute(output_format: &str, base_dir: &str) -> Result<()> {
-    let repos = find_git_repositories(base_dir)?;
+/// The number of files with uncommitted changes, by kind.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChangeCounts {
+    pub staged: usize,
+    pub unstaged: usize,
+    pub conflicted: usize,
+    pub untracked: usize,
+}
 
-    match output_format {
-        "tree" => print_tree(&repos, base_dir),
-        "flat" => print_flat(&repos),
-        "dump" => print_dump(&repos),
-        _ => return Err(anyhow::anyhow!("Invalid output format: {}", output_format)),
+impl ChangeCounts {
+    pub fn is_empty(&self) -> bool {
+        *self == ChangeCounts::default()
     }
+}
 
-    Ok(())
+pub struct Config {
+    pub output_format: String,
+    pub color: String,
+    pub base_dir: String,
+    pub fetch: Option<Duration>,
+    pub filter: Filter,
+    pub discovery: Discovery,
+    /// Where the repository index is kept between runs; no index is used if unset.
+    pub cache_dir: Option<PathBuf>,
+    /// Rebuild the index instead of reusing it.
+    pub refresh: bool,
 }
 
-fn find_git_repositories(base_dir: &str) -> Result<Vec<RepoStatus>> {
-    let mut repos = Vec::new();
-    find_repos_recursive(Path::new(base_dir), &mut repos)?;
-    repos.sort_by(|a, b| a.path.cmp(&b.path));
-    Ok(repos)
+/// Selects which repositories are shown. A repository is kept if it matches any of the enabled
+/// conditions; with none enabled, every repository is kept.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Filter {
+    pub dirty: bool,
+    pub ahead: bool,
+    pub behind: bool,
+    pub no_upstream: bool,
+    pub only_problems: bool,
 }
 
-fn find_repos_recursive(dir: &Path, repos: &mut Vec<RepoStatus>) -> Result<()> {
-    if !dir.is_dir() {
-        return Ok(());
+impl Filter {
+    fn is_active(&self) -> bool {
+        self.dirty || self.ahead || self.behind || self.no_upstream || self.only_problems
     }
 
-    let git_dir = dir.join(".git");
-    if git_dir.exists() {
-        if git_dir.is_dir() {
-            if let Ok(status) = get_repo_status(dir) {
-                repos.push(status);
-            }
+    fn matches(&self, repo: &RepoStatus) -> bool {
+        if !self.is_active() {
+            return true;
+        }
+
+        let any = |f: fn(&BranchStatus) -> bool| repo.all_branches.iter().any(|b| f(&b.status));
+        let dirty = !repo.changes.is_empty() || repo.submodules.iter().any(|s| s.dirty);
+        let ahead = any(|s| matches!(s, BranchStatus::Ahead { .. } | BranchStatus::Diverged { .. }));
+        let behind = any(|s| matches!(s, BranchStatus::Behind { .. } | BranchStatus::Diverged { .. }));
+        let no_upstream = any(|s| matches!(s, BranchStatus::NoUpstream | BranchStatus::UpstreamGone));
+        let problem = dirty
+            || ahead
+            || behind
+            || no_upstream
+            || repo.fetch_error.is_some()
+            || repo.submodule_error.is_some()
+            || repo.submodules.iter().any(|s| !s.is_ok());
+
+        (self.dirty && dirty)
+            || (self.ahead && ahead)
+            || (self.behind && behind)
+            || (self.no_upstream && no_upstream)
+            || (self.only_problems && problem)
+    }
+}
+
+pub fn execute(config: &Config) -> Result<()> {
+    let base_dir = normalize_path(Path::new(&config.base_dir));
+    let base_dir = base_dir.to_string_lossy();
+    let base_dir = base_dir.as_ref();
+    let output_format = config.output_format.as_str();
+    let color = color_choice(&config.color)?;
+    if !matches!(output_format, "tree" | "flat" | "dump" | "json" | "ndjson") {
+        return Err(anyhow::anyhow!("Invalid output format: {}", output_format));
+    }
+
+    // NDJSON lines are written as soon as each repository is evaluated, in no particular order
+    let mut stream_error = None;
+    let mut stream = |repo: &RepoStatus| {
+        if output_format == "ndjson"
+            && stream_error.is_none()
+            && config.filter.matches(repo)
+            && !(config.discovery.submodules && config.discovery.nested && is_submodule_checkout(&repo.path, Path::new(base_dir)))
+            && let Err(e) = print_ndjson(repo)
+        {
+            stream_error = Some(e);
         }
-        return Ok(());
+    };
+    let mut repos = match &config.cache_dir {
+        Some(cache_dir) => find_git_repositories_cached(
+            base_dir,
+            &config.discovery,
+            config.fetch,
+            cache_dir,
+            config.refresh,
+            &mut stream,
+        )?,
+        None => stream_git_repositories(base_dir, &config.discovery, config.fetch, &mut stream)?,
+    };
+    if let Some(e) = stream_error {
+        return Err(e);
+    }
+    repos.retain(|repo| config.filter.matches(repo));
+
+    match output_format {
+        "tree" => print_tree(&repos, base_dir, config.filter.is_active(), color),
+        "flat" => print_flat(&repos, color),
+        "dump" => print_dump(&repos, base_dir)?,
+        "json" => print_json(&repos)?,
+        _ => {}
     }
 
-    if let Ok(entries) = fs::read_dir(dir) {
-        for entry in entries.flatten() {
-            let name = entry.file_name();
+    Ok(())
+}
+
+/// Discover repositories under `base_dir`, skipping directories excluded by `discovery`, and
+/// evaluate their status. When `fetch` is set, all remotes of each repository are fetched first,
+/// giving up after the given timeout.
+pub fn find_git_repositories(
+    base_dir: &str,
+    discovery: &Discovery,
+    fetch: Option<Duration>,
+) -> Result<Vec<RepoStatus>> {
+    stream_git_repositories(base_dir, discovery, fetch, &mut |_| {})
+}
+
+/// Like [`find_git_repositories`], also passing each status to `each` as soon as it is evaluated.
+fn stream_git_repositories(
+    base_dir: &str,
+    discovery: &Discovery,
+    fetch: Option<Duration>,
+    each: &mut dyn FnMut(&RepoStatus),
+) -> Result<Vec<RepoStatus>> {
+    let base_dir = normalize_path(Path::new(base_dir));
+    let status = |path: &Path| evaluate_repo(path, discovery, fetch).ok();
+    let (repos, _) = evaluate(&status, |repos| walk(&base_dir, discovery, repos), each)?;
+    Ok(finish(repos, discovery))
+}
+
+/// Like [`stream_git_repositories`], but reuses what the index in `cache_dir` recorded during the
+/// previous run: its repository paths if no searched directory changed since, and the status of
+/// each repository that didn't change, unless `fetch` is set. The index is then updated. With
+/// `refresh`, it is rebuilt from scratch.
+pub fn find_git_repositories_cached(
+    base_dir: &str,
+    discovery: &Discovery,
+    fetch: Option<Duration>,
+    cache_dir: &Path,
+    refresh: bool,
+    each: &mut dyn FnMut(&RepoStatus),
+) -> Result<Vec<RepoStatus>> {
+    let base_dir = normalize_path(Path::new(base_dir));
+    let options = format!("{:?}", discovery);
+    let previous = (!refresh)
+        .then(|| cache::Index::load(cache_dir, &base_dir, &options))
+        .flatten();
 
-            if let Some(name_str) = name.to_str() {
-                if name_str.starts_with('.') {
-                    continue;
+    // Repositories that fail to evaluate are kept in the index, so they are retried next time
+    let status = |path: &Path| -> Option<(PathBuf, Option<cache::Entry>)> {
+        if fetch.is_none()
+            && let Some(mut entry) = previous.as_ref().and_then(|index| index.entry(path)).cloned()
+            && refresh_worktree(&mut entry.status, discovery).is_ok()
+        {
+            entry.status.fetch_error = None;
+            return Some((path.to_path_buf(), Some(entry)));
+        }
+        let entry = cache::Entry::load(path, |path| evaluate_repo(path, discovery, fetch));
+        Some((path.to_path_buf(), entry.ok()))
+    };
+    let mut each = |(_, entry): &(PathBuf, Option<cache::Entry>)| {
+        if let Some(entry) = entry {
+            each(&entry.status);
+        }
+    };
+    let (evaluated, directories) = match previous.as_ref().filter(|index| index.is_current()) {
+        Some(index) => evaluate(
+            &status,
+            |repos| {
+                for path in index.paths() {
+                    let _ = repos.send(path.to_path_buf());
                 }
+                Ok(index.directories().to_vec())
+            },
+            &mut each,
+        )?,
+        None => evaluate(&status, |repos| walk(&base_dir, discovery, repos), &mut each)?,
+    };
+
+    let (paths, entries): (Vec<_>, Vec<_>) = evaluated.into_iter().unzip();
+    let entries = entries.into_iter().flatten().collect();
+    let index = cache::Index::new(&base_dir, options, directories, paths, entries);
+    if let Err(e) = index.save(cache_dir) {
+        eprintln!("warning: failed to save the repository index: {:#}", e);
+    }
+    Ok(finish(index.into_statuses(), discovery))
+}
+
+/// Run `discover` and evaluate `status` for every repository path it sends on a pool of
+/// workers, so statuses are computed while discovery is still going. Each result is passed to
+/// `each` on this thread as it comes in.
+fn evaluate<R: Send, T: Send>(
+    status: &(dyn Fn(&Path) -> Option<R> + Sync),
+    discover: impl FnOnce(mpsc::Sender<PathBuf>) -> Result<T> + Send,
+    each: &mut dyn FnMut(&R),
+) -> Result<(Vec<R>, T)> {
+    let jobs = thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+    let (path_tx, path_rx) = mpsc::channel::<PathBuf>();
+    let (status_tx, status_rx) = mpsc::channel();
+    let path_rx = &Mutex::new(path_rx);
 
-                if matches!(name_str, "node_modules" | "target" | "build" | "dist" | "out" | "__pycache__" | ".cache" | "vendor" | "bin" | "obj") {
-                    continue;
+    thread::scope(move |scope| {
+        for _ in 0..jobs {
+            let status_tx = status_tx.clone();
+            scope.spawn(move || {
+                loop {
+                    let next = path_rx.lock().unwrap().recv();
+                    let Ok(path) = next else { break };
+                    if let Some(status) = status(&path) {
+                        let _ = status_tx.send(status);
+                    }
                 }
+            });
+        }
+        drop(status_tx);
+        let discovery = scope.spawn(move || discover(path_tx));
+
+        let results = status_rx
+            .into_iter()
+            .inspect(|result| each(result))
+            .collect();
+        let discovered = discovery.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic))?;
+        Ok((results, discovered))
+    })
+}
+
+fn evaluate_repo(path: &Path, discovery: &Discovery, fetch: Option<Duration>) -> Result<RepoStatus> {
+    let mut status = load_repo_status(path, fetch)?;
+    if discovery.submodules {
+        add_submodules(&mut status, &gix::open(path)?);
+    }
+    Ok(status)
+}
+
+/// Count the working-tree changes of a cached `status` again, along with the state of its
+/// submodules, since editing a file leaves no trace the cache could check.
+fn refresh_worktree(status: &mut RepoStatus, discovery: &Discovery) -> Result<()> {
+    let repo = gix::open(&status.path)?;
+    if repo.workdir().is_some() {
+        status.changes = count_changes(&repo)?;
+    }
+    if discovery.submodules {
+        add_submodules(status, &repo);
+    }
+    Ok(())
+}
+
+fn add_submodules(status: &mut RepoStatus, repo: &gix::Repository) {
+    (status.submodules, status.submodule_error) = match get_submodules(repo) {
+        Ok(submodules) => (submodules, None),
+        Err(e) => (Vec::new(), Some(format!("{:#}", e))),
+    };
+}
+
+/// Whether `path` is the checkout of a submodule of a repository further up, but still under
+/// `base_dir`.
+fn is_submodule_checkout(path: &Path, base_dir: &Path) -> bool {
+    path.ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(base_dir))
+        .filter(|dir| dir.join(".git").exists())
+        .filter_map(|dir| gix::open(dir).ok())
+        .any(|repo| {
+            repo.submodules()
+                .ok()
+                .flatten()
+                .is_some_and(|mut submodules| submodules.any(|submodule| submodule.work_dir().is_ok_and(|dir| dir == path)))
+        })
+}
+
+/// Sort `repos` by path, leaving out checkouts of submodules reported by their superproject.
+fn finish(mut repos: Vec<RepoStatus>, discovery: &Discovery) -> Vec<RepoStatus> {
+    if discovery.submodules {
+        let checkouts: HashSet<PathBuf> = repos
+            .iter()
+            .flat_map(|repo| repo.submodules.iter().map(|submodule| repo.path.join(&submodule.path)))
+            .collect();
+        repos.retain(|repo| !checkouts.contains(&repo.path));
+    }
+    repos.sort_by(|a, b| a.path.cmp(&b.path));
+    repos
+}
+
+/// Send every repository under `base_dir` that `discovery` allows to `repos`, returning the
+/// directories that were searched.
+fn walk(base_dir: &Path, discovery: &Discovery, repos: mpsc::Sender<PathBuf>) -> Result<Vec<Stamp>> {
+    let mut walk = Walk {
+        base_dir,
+        rules: discovery.ignore_rules(base_dir)?,
+        max_depth: discovery.max_depth,
+        nested: discovery.nested,
+        follow_symlinks: discovery.follow_symlinks,
+        visited: HashSet::new(),
+        directories: vec![Stamp::of(&base_dir.join(IGNORE_FILE))],
+        repos,
+    };
+    walk.find_repos_recursive(base_dir, 0)?;
+    Ok(walk.directories)
+}
+
+/// Directories that are not searched unless a `.gitlistignore` file replaces these rules.
+const DEFAULT_IGNORE: &[&str] = &["node_modules/", "__pycache__/"];
+
+/// Name of the file at the root holding gitignore-style rules for directories not to search.
+pub const IGNORE_FILE: &str = ".gitlistignore";
+
+/// Controls which directories under the root are searched for repositories.
+#[derive(Debug, Clone, Default)]
+pub struct Discovery {
+    /// Gitignore-style patterns, relative to the root, for directories to skip.
+    pub exclude: Vec<String>,
+    /// Patterns for directories to search even when an earlier rule excludes them.
+    pub include: Vec<String>,
+    /// How many levels below the root to look for repositories; unlimited if unset.
+    pub max_depth: Option<usize>,
+    /// Keep searching inside the working trees of discovered repositories.
+    pub nested: bool,
+    /// Report the submodules of each repository. Checkouts of these are then not listed as
+    /// repositories of their own.
+    pub submodules: bool,
+    /// Search symlinked directories too. Each directory is still searched only once.
+    pub follow_symlinks: bool,
+}
+
+impl Discovery {
+    /// Combine the rules of `.gitlistignore` in `base_dir` (or the defaults if there is none)
+    /// with `exclude`, then `include`. Later rules take precedence, as in `.gitignore`.
+    fn ignore_rules(&self, base_dir: &Path) -> Result<gix::ignore::Search> {
+        let path = base_dir.join(IGNORE_FILE);
+        let file_rules: Vec<String> = match fs::read_to_string(&path) {
+            Ok(content) => content.lines().map(str::to_string).collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                DEFAULT_IGNORE.iter().map(|rule| rule.to_string()).collect()
             }
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
 
-            if entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false) {
-                find_repos_recursive(&entry.path(), repos)?;
+        let rules: Vec<String> = file_rules
+            .into_iter()
+            .chain(self.exclude.iter().cloned())
+            .chain(self.include.iter().map(|pattern| format!("!{}", pattern)))
+            .collect();
+        Ok(gix::ignore::Search::from_overrides(rules, Default::default()))
+    }
+}
+
+struct Walk<'a> {
+    base_dir: &'a Path,
+    rules: gix::ignore::Search,
+    max_depth: Option<usize>,
+    nested: bool,
+    follow_symlinks: bool,
+    /// Directories entered so far, to not loop through symlinks pointing back up the tree.
+    visited: HashSet<DirId>,
+    /// Directories searched for repositories, as they were when searched.
+    directories: Vec<Stamp>,
+    repos: mpsc::Sender<PathBuf>,
+}
+
+impl Walk<'_> {
+    fn is_ignored(&self, dir: &Path) -> bool {
+        let Ok(relative) = dir.strip_prefix(self.base_dir) else { return false };
+        let relative = gix::path::to_unix_separators_on_windows(gix::path::into_bstr(relative));
+        self.rules
+            .pattern_matching_relative_path(relative.as_ref(), Some(true), gix::glob::pattern::Case::Sensitive)
+            .is_some_and(|m| !m.pattern.is_negative())
+    }
+
+    fn find_repos_recursive(&mut self, dir: &Path, depth: usize) -> Result<()> {
+        let Ok(metadata) = fs::metadata(dir) else { return Ok(()) };
+        if !metadata.is_dir() {
+            return Ok(());
+        }
+        if self.follow_symlinks
+            && let Some(id) = dir_id(dir, &metadata)
+            && !self.visited.insert(id)
+        {
+            return Ok(());
+        }
+
+        if is_bare_repository(dir) {
+            let _ = self.repos.send(dir.to_path_buf());
+            return Ok(());
+        }
+        // `.git` is a directory for regular repositories and a file pointing elsewhere for linked
+        // worktrees and submodules
+        if dir.join(".git").exists() {
+            let _ = self.repos.send(dir.to_path_buf());
+            if !self.nested {
+                return Ok(());
+            }
+        }
+        self.directories.push(Stamp::new(dir, Some(&metadata)));
+
+        if self.max_depth.is_some_and(|max| depth >= max) {
+            return Ok(());
+        }
+
+        let Ok(entries) = fs::read_dir(dir) else { return Ok(()) };
+        let mut subdirs: Vec<(bool, PathBuf)> = entries
+            .flatten()
+            .filter(|entry| entry.file_name() != ".git")
+            .filter_map(|entry| {
+                let file_type = entry.file_type().ok()?;
+                let path = entry.path();
+                let is_symlink = file_type.is_symlink();
+                let is_dir = if is_symlink { self.follow_symlinks && path.is_dir() } else { file_type.is_dir() };
+                is_dir.then_some((is_symlink, path))
+            })
+            .collect();
+        // Real directories go first so a repository reachable both directly and through a
+        // symlink is reported at its real location.
+        subdirs.sort();
+
+        for (_, path) in subdirs {
+            if !self.is_ignored(&path) {
+                self.find_repos_recursive(&path, depth + 1)?;
             }
         }
+
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+type DirId = (u64, u64);
+#[cfg(not(unix))]
+type DirId = PathBuf;
+
+/// Identifies the directory `path` resolves to, given its `metadata`: its device and inode,
+/// where available.
+#[cfg(unix)]
+fn dir_id(_path: &Path, metadata: &fs::Metadata) -> Option<DirId> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn dir_id(path: &Path, _metadata: &fs::Metadata) -> Option<DirId> {
+    fs::canonicalize(path).ok()
+}
+
+/// `path` relative to `base_dir`, also if only one of them is spelled through a symlink.
+pub(crate) fn relative_path(path: &Path, base_dir: &Path) -> Option<PathBuf> {
+    if let Ok(relative) = path.strip_prefix(base_dir) {
+        return Some(relative.to_path_buf());
     }
+    let (path, base_dir) = (fs::canonicalize(path).ok()?, fs::canonicalize(base_dir).ok()?);
+    path.strip_prefix(base_dir).ok().map(Path::to_path_buf)
+}
 
-    Ok(())
+fn is_bare_repository(dir: &Path) -> bool {
+    dir.join("HEAD").is_file() && dir.join("objects").is_dir() && dir.join("refs").is_dir()
 }
 
 fn extract_branch_name(repo: &gix::Repository) -> Result<String> {
@@ -102,115 +573,206 @@ fn extract_branch_name(repo: &gix::Repository) -> Result<String> {
         .unwrap_or_else(|| "HEAD".to_string()))
 }
 
+/// Status of the repository at `repo_path`, fetching its remotes first when `fetch` is given.
+/// A failed fetch is recorded in `fetch_error` rather than returned.
+pub fn load_repo_status(repo_path: &Path, fetch: Option<Duration>) -> Result<RepoStatus> {
+    let fetch_error = fetch
+        .and_then(|timeout| fetch_remotes(repo_path, timeout).err())
+        .map(|e| format!("{:#}", e));
+    let mut status = get_repo_status(repo_path)?;
+    status.fetch_error = fetch_error;
+    Ok(status)
+}
+
 fn get_repo_status(repo_path: &Path) -> Result<RepoStatus> {
     let repo = gix::open(repo_path)?;
     let current_branch = extract_branch_name(&repo)?;
-    let (uncommitted_count, untracked_count) = count_changes(&repo)?;
-    let all_branches = get_all_branches(&repo, uncommitted_count, untracked_count)?;
+    let changes = if repo.workdir().is_some() {
+        count_changes(&repo)?
+    } else {
+        ChangeCounts::default()
+    };
+    let all_branches = get_all_branches(&repo)?;
+    let remotes = get_remotes(&repo);
 
     Ok(RepoStatus {
         path: repo_path.to_path_buf(),
+        kind: repo_kind(&repo),
         current_branch,
         all_branches,
+        changes,
+        remotes,
+        fetch_error: None,
+        submodules: Vec::new(),
+        submodule_error: None,
     })
 }
 
-fn count_changes(repo: &gix::Repository) -> Result<(usize, usize)> {
-    let work_dir = repo.workdir().unwrap_or(repo.path());
-    let output = std::process::Command::new("git")
-        .args(&["-C", work_dir.to_string_lossy().as_ref(), "status", "--porcelain"])
-        .output()?;
+/// How long an interrupted fetch gets to stop before the status is computed regardless.
+const FETCH_INTERRUPT_GRACE: Duration = Duration::from_secs(5);
+
+/// Fetch every remote of the repository at `repo_path`, failing if it takes longer than `timeout`.
+fn fetch_remotes(repo_path: &Path, timeout: Duration) -> Result<()> {
+    let path = repo_path.to_path_buf();
+    let should_interrupt = Arc::new(AtomicBool::new(false));
+    let (tx, rx) = mpsc::channel();
+
+    // The fetch runs detached so a hung connection can't hold up the worker past the timeout.
+    let interrupt = should_interrupt.clone();
+    thread::spawn(move || {
+        let _ = tx.send(fetch_all_remotes(&path, &interrupt));
+    });
+
+    if let Ok(result) = rx.recv_timeout(timeout) {
+        return result;
+    }
+
+    // Give the fetch a moment to notice the interrupt, so the status isn't computed while it
+    // is still updating references. A connection that doesn't respond is abandoned.
+    should_interrupt.store(true, Ordering::Relaxed);
+    let timed_out = anyhow::anyhow!("Fetch timed out after {}s", timeout.as_secs());
+    match rx.recv_timeout(FETCH_INTERRUPT_GRACE) {
+        Ok(Err(e)) => Err(e.context(timed_out)),
+        Ok(Ok(())) | Err(_) => Err(timed_out),
+    }
+}
+
+/// Fetch every remote of the repository, carrying on past remotes that fail.
+fn fetch_all_remotes(repo_path: &Path, should_interrupt: &AtomicBool) -> Result<()> {
+    let mut repo = gix::open(repo_path)?;
+    // Updating remote-tracking refs writes reflog entries, which need a committer.
+    repo.committer_or_set_generic_fallback()?;
 
-    let (mut uncommitted, mut untracked) = (0, 0);
-    for line in output.stdout.split(|&b| b == b'\n').filter(|l| l.len() >= 2) {
-        match (line[0], line[1]) {
-            (b'?', b'?') => untracked += 1,
-            (b' ', b' ') => {},
-            _ => uncommitted += 1,
+    let mut errors = Vec::new();
+    for name in repo.remote_names().iter() {
+        let fetch = || -> Result<()> {
+            repo.find_remote(name.as_ref())?
+                .connect(gix::remote::Direction::Fetch)?
+                .prepare_fetch(gix::progress::Discard, Default::default())?
+                .receive(gix::progress::Discard, should_interrupt)?;
+            Ok(())
+        };
+        if let Err(e) = fetch() {
+            errors.push(format!("Failed to fetch {}: {:#}", name, e));
         }
     }
-    Ok((uncommitted, untracked))
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(errors.join("; ")))
+    }
 }
 
-fn get_all_branches(
-    repo: &gix::Repository,
-    uncommitted: usize,
-    untracked: usize,
-) -> Result<Vec<BranchInfo>> {
-    let mut branches = Vec::new();
+fn repo_kind(repo: &gix::Repository) -> RepoKind {
+    match repo.kind() {
+        gix::repository::Kind::WorkTree { is_linked: false } => RepoKind::Main,
+        gix::repository::Kind::WorkTree { is_linked: true } => {
+            let main = repo
+                .main_repo()
+                .ok()
+                .map(|main| main.workdir().unwrap_or(main.git_dir()).to_path_buf())
+                .unwrap_or_else(|| repo.common_dir().to_path_buf());
+            RepoKind::Worktree {
+                main: fs::canonicalize(&main).unwrap_or(main),
+            }
+        }
+        gix::repository::Kind::Submodule => RepoKind::Submodule,
+        gix::repository::Kind::Bare => RepoKind::Bare,
+    }
+}
+
+/// A short marker for anything but a regular repository.
+fn kind_label(kind: &RepoKind) -> Option<String> {
+    match kind {
+        RepoKind::Main => None,
+        RepoKind::Worktree { main } => Some(format!("worktree of {}", main.display())),
+        RepoKind::Submodule => Some("submodule".to_string()),
+        RepoKind::Bare => Some("bare".to_string()),
+    }
+}
 
-    let current_branch_name = extract_branch_name(&repo)?;
+fn get_submodules(repo: &gix::Repository) -> Result<Vec<SubmoduleStatus>> {
+    let Some(submodules) = repo.submodules()? else { return Ok(Vec::new()) };
 
-    let mut branch_statuses = HashMap::new();
-    
-    if let Ok(refs) = repo.references() {
-        if let Ok(branches) = refs.local_branches() {
-            for branch in branches.flatten() {
-                if let Some((category, short_name)) = branch.name().category_and_short_name() {
-                    if matches!(category, gix::reference::Category::LocalBranch) {
-                        branch_statuses.insert(short_name.to_string(), BranchStatus::Ok);
+    submodules
+        .map(|submodule| {
+            let status = submodule.status(submodule.ignore()?.unwrap_or_default(), false)?;
+            let initialized = status.state.repository_exists && status.state.worktree_checkout;
+            Ok(SubmoduleStatus {
+                path: gix::path::from_bstr(submodule.path()?).into_owned(),
+                initialized,
+                out_of_date: initialized && status.index_id.is_some() && status.checked_out_head_id != status.index_id,
+                dirty: status.changes.is_some_and(|changes| !changes.is_empty()),
+            })
+        })
+        .collect()
+}
+
+fn get_remotes(repo: &gix::Repository) -> Vec<RemoteInfo> {
+    repo.remote_names()
+        .iter()
+        .map(|name| RemoteInfo {
+            name: name.to_string(),
+            url: repo
+                .find_remote(name.as_ref())
+                .ok()
+                .and_then(|remote| remote.url(gix::remote::Direction::Fetch).map(|url| url.to_bstring().to_string())),
+        })
+        .collect()
+}
+
+fn count_changes(repo: &gix::Repository) -> Result<ChangeCounts> {
+    use gix::status::index_worktree::Item as WorktreeItem;
+    use gix::status::plumbing::index_as_worktree::EntryStatus;
+
+    let mut counts = ChangeCounts::default();
+    let mut conflicted = HashSet::new();
+    let mut staged = Vec::new();
+
+    let status = repo
+        .status(gix::progress::Discard)?
+        .untracked_files(gix::status::UntrackedFiles::Collapsed)
+        .into_iter(None)?;
+
+    for item in status {
+        match item? {
+            gix::status::Item::TreeIndex(change) => staged.push(change.location().to_owned()),
+            gix::status::Item::IndexWorktree(item) => match item {
+                WorktreeItem::Modification { status: EntryStatus::Conflict(_), rela_path, .. } => {
+                    conflicted.insert(rela_path);
+                }
+                WorktreeItem::Modification { status: EntryStatus::NeedsUpdate(_), .. } => {}
+                WorktreeItem::Modification { .. } | WorktreeItem::Rewrite { .. } => counts.unstaged += 1,
+                WorktreeItem::DirectoryContents { entry, .. } => {
+                    if entry.status == gix::dir::entry::Status::Untracked {
+                        counts.untracked += 1;
                     }
                 }
-            }
+            },
         }
     }
-    
-    if !branch_statuses.is_empty() {
-        let work_dir = repo.workdir().unwrap_or(repo.path());
-        if let Ok(output) = std::process::Command::new("git")
-            .args(&[
-                "-C",
-                work_dir.to_string_lossy().as_ref(),
-                "for-each-ref",
-                "--format=%(refname:short) %(upstream:track)",
-                "refs/heads",
-            ])
-            .output()
-        {
-            let output_str = String::from_utf8_lossy(&output.stdout);
-            for line in output_str.lines() {
-                let mut parts_iter = line.split_whitespace();
-                let Some(branch_name) = parts_iter.next() else {
-                    continue;
-                };
-
-                let status = match (
-                    parts_iter.next(),
-                    parts_iter.next(),
-                    parts_iter.next(),
-                    parts_iter.next(),
-                ) {
-                    (None, _, _, _) => BranchStatus::Ok,
-                    (Some("[ahead"), Some(count_str), Some("behind"), Some(behind_str)) => {
-                        let ahead = count_str
-                            .trim_end_matches(',')
-                            .parse::<usize>()
-                            .unwrap_or(0);
-                        let behind = behind_str
-                            .trim_end_matches(']')
-                            .parse::<usize>()
-                            .unwrap_or(0);
-                        BranchStatus::Diverged { ahead, behind }
-                    }
-                    (Some("[ahead"), Some(count_str), _, _) => {
-                        let count = count_str
-                            .trim_end_matches(']')
-                            .parse::<usize>()
-                            .unwrap_or(0);
-                        BranchStatus::Ahead(count)
-                    }
-                    (Some("[behind"), Some(count_str), _, _) => {
-                        let count = count_str
-                            .trim_end_matches(']')
-                            .parse::<usize>()
-                            .unwrap_or(0);
-                        BranchStatus::Behind(count)
-                    }
-                    _ => BranchStatus::NoUpstream,
-                };
 
-                branch_statuses.entry(branch_name.to_string()).and_modify(|e| *e = status);
-            }
+    // Conflicted paths also differ between HEAD and the index, but are reported only once.
+    counts.staged = staged.iter().filter(|path| !conflicted.contains(*path)).count();
+    counts.conflicted = conflicted.len();
+    Ok(counts)
+}
+
+fn get_all_branches(repo: &gix::Repository) -> Result<Vec<BranchInfo>> {
+    let mut branches = Vec::new();
+
+    let current_branch_name = extract_branch_name(repo)?;
+
+    let mut branch_statuses = BTreeMap::new();
+
+    if let Ok(refs) = repo.references()
+        && let Ok(local_branches) = refs.local_branches()
+    {
+        for mut branch in local_branches.flatten() {
+            let short_name = branch.name().shorten().to_string();
+            let status = get_branch_status(repo, &mut branch).unwrap_or(BranchStatus::NoUpstream);
+            branch_statuses.insert(short_name, status);
         }
     }
 
@@ -219,31 +781,10 @@ fn get_all_branches(
         .cloned()
         .unwrap_or(BranchStatus::NoUpstream);
 
-    if uncommitted > 0 || untracked > 0 {
-        branches.push(BranchInfo {
-            name: current_branch_name.clone(),
-            status: current_status,
-        });
-
-        if uncommitted > 0 {
-            branches.push(BranchInfo {
-                name: String::new(),
-                status: BranchStatus::Uncommitted { count: uncommitted },
-            });
-        }
-
-        if untracked > 0 {
-            branches.push(BranchInfo {
-                name: String::new(),
-                status: BranchStatus::Untracked { count: untracked },
-            });
-        }
-    } else {
-        branches.push(BranchInfo {
-            name: current_branch_name.clone(),
-            status: current_status,
-        });
-    }
+    branches.push(BranchInfo {
+        name: current_branch_name.clone(),
+        status: current_status,
+    });
 
     for (branch_name, status) in branch_statuses {
         if branch_name != current_branch_name {
@@ -257,13 +798,61 @@ fn get_all_branches(
     Ok(branches)
 }
 
-fn print_tree(repos: &[RepoStatus], base_dir: &str) {
-    let mut stdout = StandardStream::stdout(ColorChoice::Always);
+fn get_branch_status(repo: &gix::Repository, branch: &mut gix::Reference<'_>) -> Result<BranchStatus> {
+    let Some(tracking_name) = repo.branch_remote_tracking_ref_name(branch.name(), gix::remote::Direction::Fetch) else {
+        return Ok(BranchStatus::NoUpstream);
+    };
+    let Some(mut upstream) = repo.try_find_reference(tracking_name?.as_ref())? else {
+        return Ok(BranchStatus::UpstreamGone);
+    };
+
+    let local_id = branch.peel_to_id_in_place()?.detach();
+    let upstream_id = upstream.peel_to_id_in_place()?.detach();
+    let ahead = count_commits_between(repo, local_id, upstream_id)?;
+    let behind = count_commits_between(repo, upstream_id, local_id)?;
+
+    Ok(match (ahead, behind) {
+        (0, 0) => BranchStatus::Ok,
+        (ahead, 0) => BranchStatus::Ahead { ahead },
+        (0, behind) => BranchStatus::Behind { behind },
+        (ahead, behind) => BranchStatus::Diverged { ahead, behind },
+    })
+}
+
+/// Count the commits reachable from `tip` that aren't reachable from `hidden`.
+fn count_commits_between(repo: &gix::Repository, tip: gix::ObjectId, hidden: gix::ObjectId) -> Result<usize> {
+    let walk = repo.rev_walk([tip]).with_hidden([hidden]).all()?;
+    walk.into_iter().try_fold(0, |count, info| info.map(|_| count + 1)).map_err(Into::into)
+}
+
+/// Resolve a `--color` value. `auto` colors only when stdout is a terminal and `NO_COLOR` is unset.
+fn color_choice(color: &str) -> Result<ColorChoice> {
+    match color {
+        "always" => Ok(ColorChoice::Always),
+        "never" => Ok(ColorChoice::Never),
+        "auto" => {
+            let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+            if no_color || !std::io::stdout().is_terminal() {
+                Ok(ColorChoice::Never)
+            } else {
+                Ok(ColorChoice::Auto)
+            }
+        }
+        _ => Err(anyhow::anyhow!("Invalid color choice: {}", color)),
+    }
+}
+
+fn print_tree(repos: &[RepoStatus], base_dir: &str, filtered: bool, color: ColorChoice) {
+    let mut stdout = StandardStream::stdout(color);
     
     writeln!(stdout, "{}", base_dir).unwrap();
 
     if repos.is_empty() {
-        writeln!(stdout, "  No git repositories found").unwrap();
+        if filtered {
+            writeln!(stdout, "  No git repositories match the given filters").unwrap();
+        } else {
+            writeln!(stdout, "  No git repositories found").unwrap();
+        }
         return;
     }
 
@@ -277,6 +866,8 @@ struct TreeNode {
     name: String,
     children: HashMap<String, TreeNode>,
     repo_status: Option<RepoStatus>,
+    submodule: Option<SubmoduleStatus>,
+    label: Option<String>,
 }
 
 impl TreeNode {
@@ -285,31 +876,56 @@ impl TreeNode {
             name,
             children: HashMap::new(),
             repo_status: None,
+            submodule: None,
+            label: None,
         }
     }
+
+    /// The descendant reached by following `names`, creating any missing nodes on the way.
+    fn descendant(&mut self, names: Vec<String>) -> &mut TreeNode {
+        names.into_iter().fold(self, |node, name| {
+            node.children
+                .entry(name.clone())
+                .or_insert_with(|| TreeNode::new(name))
+        })
+    }
 }
 
+/// Arrange `repos` by their path under `base_dir`. Linked worktrees whose main repository is
+/// also listed are placed underneath it instead, and submodules underneath their superproject.
 fn build_tree_structure(repos: &[RepoStatus], base_dir: &str) -> TreeNode {
     let mut root = TreeNode::new(String::new());
+    let names = |path: &Path| -> Option<Vec<String>> {
+        let relative = relative_path(path, Path::new(base_dir))?;
+        Some(relative.components().map(|c| c.as_os_str().to_string_lossy().to_string()).collect())
+    };
+    let listed: HashMap<PathBuf, &Path> = repos
+        .iter()
+        .filter(|repo| !matches!(repo.kind, RepoKind::Worktree { .. }))
+        .map(|repo| (fs::canonicalize(&repo.path).unwrap_or_else(|_| repo.path.clone()), repo.path.as_path()))
+        .collect();
 
     for repo in repos {
-        if let Ok(relative_path) = repo.path.strip_prefix(base_dir) {
-            let components: Vec<_> = relative_path.components().collect();
-            let components_len = components.len();
+        let mut path = names(&repo.path).unwrap_or_else(|| vec![repo.path.display().to_string()]);
+        let mut label = kind_label(&repo.kind);
 
-            let mut current_node = &mut root;
+        if let RepoKind::Worktree { main } = &repo.kind
+            && let Some(main_names) = listed.get(main).and_then(|main_path| names(main_path))
+        {
+            path = [main_names, vec![path.join("/")]].concat();
+            label = Some("worktree".to_string());
+        }
 
-            for (i, component) in components.iter().enumerate() {
-                let component_str = component.as_os_str().to_string_lossy();
-                current_node = current_node
-                    .children
-                    .entry(component_str.to_string())
-                    .or_insert_with(|| TreeNode::new(component_str.to_string()));
+        let node = root.descendant(path);
+        node.repo_status = Some(repo.clone());
+        node.label = label;
 
-                if i == components_len - 1 {
-                    current_node.repo_status = Some(repo.clone());
-                }
-            }
+        for submodule in &repo.submodules {
+            let child = node.descendant(
+                submodule.path.components().map(|c| c.as_os_str().to_string_lossy().to_string()).collect(),
+            );
+            child.submodule = Some(submodule.clone());
+            child.label = Some("submodule".to_string());
         }
     }
 
@@ -320,26 +936,37 @@ fn print_tree_node(node: &TreeNode, prefix: &str, is_last: bool, out: &mut Stand
     if !node.name.is_empty() {
         let connector = if is_last { "└── " } else { "├── " };
         write!(out, "{}{}{}", prefix, connector, node.name).unwrap();
+        if let Some(ref label) = node.label {
+            out.set_color(ColorSpec::new().set_fg(Some(Color::Cyan))).unwrap();
+            write!(out, " [{}]", label).unwrap();
+            out.reset().unwrap();
+        }
 
         if let Some(ref status) = node.repo_status {
             let mut first_branch = true;
+            let name_width = node.name.len() + node.label.as_ref().map_or(0, |label| label.len() + 3);
 
             for branch in status.all_branches.iter() {
-                if first_branch && !branch.name.is_empty() {
+                if first_branch {
                     write!(out, " {}", branch.name).unwrap();
                     print_branch_status(&branch.status, out);
+                    print_changes(&status.changes, out);
                     first_branch = false;
-                } else if !branch.name.is_empty() {
+                } else {
                     write!(out, "\n{}", prefix).unwrap();
                     write!(out, "{}", if is_last { "    " } else { "│   " }).unwrap();
-                    write!(out, "{:width$}", "", width = 20_usize.saturating_sub(node.name.len())).unwrap();
+                    write!(out, "{:width$}", "", width = 20_usize.saturating_sub(name_width)).unwrap();
                     write!(out, "{}", branch.name).unwrap();
                     print_branch_status(&branch.status, out);
-                } else {
-                    print_branch_status(&branch.status, out);
                 }
             }
         }
+        if let Some(ref status) = node.repo_status {
+            print_errors(status, out);
+        }
+        if let Some(ref submodule) = node.submodule {
+            print_submodule_status(submodule, out);
+        }
         writeln!(out).unwrap();
     }
 
@@ -365,12 +992,11 @@ fn print_tree_node(node: &TreeNode, prefix: &str, is_last: bool, out: &mut Stand
 fn print_branch_status(status: &BranchStatus, out: &mut StandardStream) {
     let (color, text) = match status {
         BranchStatus::Ok => (Color::Green, " ok".to_string()),
-        BranchStatus::Ahead(n) => (Color::Yellow, format!(" {} ahead", n)),
-        BranchStatus::Behind(n) => (Color::Yellow, format!(" {} behind", n)),
+        BranchStatus::Ahead { ahead } => (Color::Yellow, format!(" {} ahead", ahead)),
+        BranchStatus::Behind { behind } => (Color::Yellow, format!(" {} behind", behind)),
         BranchStatus::Diverged { ahead, behind } => (Color::Yellow, format!(" {} ahead {} behind", ahead, behind)),
         BranchStatus::NoUpstream => (Color::Yellow, " no upstream".to_string()),
-        BranchStatus::Uncommitted { count } => (Color::Yellow, format!("  [ {} uncommitted ]", count)),
-        BranchStatus::Untracked { count } => (Color::Red, format!("  [ {} untracked ]", count)),
+        BranchStatus::UpstreamGone => (Color::Red, " upstream gone".to_string()),
     };
     
     out.set_color(ColorSpec::new().set_fg(Some(color))).unwrap();
@@ -378,11 +1004,66 @@ fn print_branch_status(status: &BranchStatus, out: &mut StandardStream) {
     out.reset().unwrap();
 }
 
-fn print_flat(repos: &[RepoStatus]) {
-    let mut out = StandardStream::stdout(ColorChoice::Always);
+fn print_changes(changes: &ChangeCounts, out: &mut StandardStream) {
+    let counts = [
+        (changes.conflicted, "conflicted", Color::Red),
+        (changes.staged, "staged", Color::Yellow),
+        (changes.unstaged, "unstaged", Color::Yellow),
+        (changes.untracked, "untracked", Color::Red),
+    ];
+    for (count, what, color) in counts {
+        if count > 0 {
+            out.set_color(ColorSpec::new().set_fg(Some(color))).unwrap();
+            write!(out, "  [ {} {} ]", count, what).unwrap();
+            out.reset().unwrap();
+        }
+    }
+}
+
+fn print_submodule_status(status: &SubmoduleStatus, out: &mut StandardStream) {
+    if status.is_ok() {
+        out.set_color(ColorSpec::new().set_fg(Some(Color::Green))).unwrap();
+        write!(out, " ok").unwrap();
+        out.reset().unwrap();
+        return;
+    }
+
+    let problems = [
+        (!status.initialized, "not initialized"),
+        (status.out_of_date, "out of date"),
+        (status.dirty, "dirty"),
+    ];
+    out.set_color(ColorSpec::new().set_fg(Some(Color::Yellow))).unwrap();
+    for (_, text) in problems.iter().filter(|(present, _)| *present) {
+        write!(out, " {}", text).unwrap();
+    }
+    out.reset().unwrap();
+}
+
+fn print_errors(status: &RepoStatus, out: &mut StandardStream) {
+    let errors = [
+        ("fetch failed", &status.fetch_error),
+        ("submodules unreadable", &status.submodule_error),
+    ];
+    for (what, error) in errors {
+        if let Some(error) = error {
+            out.set_color(ColorSpec::new().set_fg(Some(Color::Red))).unwrap();
+            write!(out, "  [ {}: {} ]", what, error).unwrap();
+            out.reset().unwrap();
+        }
+    }
+}
+
+fn print_flat(repos: &[RepoStatus], color: ColorChoice) {
+    let mut out = StandardStream::stdout(color);
     
     for repo in repos {
         write!(out, "{}", repo.path.display()).unwrap();
+        if let Some(label) = kind_label(&repo.kind) {
+            out.set_color(ColorSpec::new().set_fg(Some(Color::Cyan))).unwrap();
+            write!(out, " [{}]", label).unwrap();
+            out.reset().unwrap();
+        }
         if let Some(branch) = repo
             .all_branches
             .iter()
@@ -391,20 +1072,586 @@ fn print_flat(repos: &[RepoStatus]) {
             write!(out, " ({})", branch.name).unwrap();
             print_branch_status(&branch.status, &mut out);
         }
+        print_errors(repo, &mut out);
         writeln!(out).unwrap();
+
+        for submodule in &repo.submodules {
+            write!(out, "{}", repo.path.join(&submodule.path).display()).unwrap();
+            out.set_color(ColorSpec::new().set_fg(Some(Color::Cyan))).unwrap();
+            write!(out, " [submodule]").unwrap();
+            out.reset().unwrap();
+            print_submodule_status(submodule, &mut out);
+            writeln!(out).unwrap();
+        }
     }
 }
 
-fn print_dump(repos: &[RepoStatus]) {
-    let mut out = StandardStream::stdout(ColorChoice::Always);
-    
-    for repo in repos {
-        if let Ok(gix_repo) = gix::open(&repo.path) {
-            if let Ok(remote) = gix_repo.find_remote("origin") {
-                if let Some(url) = remote.url(gix::remote::Direction::Fetch) {
-                    writeln!(out, "{} {}", url.to_bstring(), repo.current_branch).unwrap();
-                }
-            }
+fn print_dump(repos: &[RepoStatus], base_dir: &str) -> Result<()> {
+    let entries: Vec<dump::Entry> = repos.iter().filter_map(|repo| dump_entry(repo, base_dir)).collect();
+    print!("{}", dump::to_string(&entries)?);
+    Ok(())
+}
+
+/// Everything needed to reproduce the repository with `git get -d`, or `None` if it has no
+/// `origin` to clone from. Only regular repositories are dumped, as worktrees and submodules
+/// belong to another repository and git-get doesn't create bare clones.
+fn dump_entry(status: &RepoStatus, base_dir: &str) -> Option<dump::Entry> {
+    if status.kind != RepoKind::Main {
+        return None;
+    }
+    let repo = gix::open(&status.path).ok()?;
+    let url = status.remotes.iter().find(|r| r.name == "origin")?.url.clone()?;
+    let path = relative_path(&status.path, Path::new(base_dir))?
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    let config = repo.config_snapshot();
+
+    Some(dump::Entry {
+        url,
+        path: Some(path),
+        branch: (status.current_branch != "HEAD").then(|| status.current_branch.clone()),
+        commit: repo.head_id().ok().map(|id| id.to_string()),
+        depth: shallow_depth(&repo),
+        filter: config.string("remote.origin.partialclonefilter").map(|f| f.to_string()),
+        single_branch: config
+            .strings("remote.origin.fetch")
+            .is_some_and(|specs| !specs.is_empty() && specs.iter().all(|spec| !spec.contains(&b'*'))),
+        sparse: worktree::sparse_dirs(&repo),
+        submodules: repo.submodules().ok().flatten().is_some_and(|mut submodules| {
+            submodules.any(|submodule| submodule.state().is_ok_and(|state| state.repository_exists))
+        }),
+        remotes: status
+            .remotes
+            .iter()
+            .filter(|r| r.name != "origin")
+            .filter_map(|r| Some((r.name.clone(), r.url.clone()?)))
+            .collect(),
+    })
+}
+
+/// The number of commits reachable from HEAD in a shallow repository.
+fn shallow_depth(repo: &gix::Repository) -> Option<NonZeroU32> {
+    if !repo.is_shallow() {
+        return None;
+    }
+    let head = repo.head_id().ok()?;
+    let count = repo.rev_walk([head]).all().ok()?.count();
+    NonZeroU32::new(u32::try_from(count).ok()?)
+}
+
+fn print_json(repos: &[RepoStatus]) -> Result<()> {
+    let mut out = std::io::stdout().lock();
+    serde_json::to_writer_pretty(&mut out, repos)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+fn print_ndjson(repo: &RepoStatus) -> Result<()> {
+    let mut out = std::io::stdout().lock();
+    serde_json::to_writer(&mut out, repo)?;
+    writeln!(out)?;
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{append_config, clone_repo, git, init_source_repo, temp_dir};
+
+    #[test]
+    fn test_find_git_repositories_sorted() {
+        let dir = temp_dir("list-sorted");
+        for name in ["zeta", "alpha/one", "mid", "alpha/two"] {
+            init_source_repo(&dir.join(name));
+        }
+
+        let mut streamed = Vec::new();
+        let repos = stream_git_repositories(dir.to_str().unwrap(), &Discovery::default(), None, &mut |repo| {
+            streamed.push(repo.path.clone())
+        })
+        .unwrap();
+        let paths: Vec<_> = repos.iter().map(|r| r.path.strip_prefix(&dir).unwrap().to_path_buf()).collect();
+        assert_eq!(
+            paths,
+            ["alpha/one", "alpha/two", "mid", "zeta"].map(PathBuf::from)
+        );
+        streamed.sort();
+        assert_eq!(streamed, repos.iter().map(|r| r.path.clone()).collect::<Vec<_>>());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_discovery_rules() {
+        let dir = temp_dir("list-discovery");
+        for name in ["bin/tool", ".config/dots", "node_modules/pkg", "deep/a/b/c", "vendor/lib"] {
+            init_source_repo(&dir.join(name));
+        }
+        let root = dir.to_str().unwrap();
+        let discover = |discovery: &Discovery| -> Vec<PathBuf> {
+            find_git_repositories(root, discovery, None)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.path.strip_prefix(&dir).unwrap().to_path_buf())
+                .collect()
+        };
+
+        assert_eq!(
+            discover(&Discovery::default()),
+            [".config/dots", "bin/tool", "deep/a/b/c", "vendor/lib"].map(PathBuf::from)
+        );
+
+        let discovery = Discovery {
+            exclude: vec![".*".to_string(), "/deep/".to_string()],
+            include: vec!["node_modules".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            discover(&discovery),
+            ["bin/tool", "node_modules/pkg", "vendor/lib"].map(PathBuf::from)
+        );
+
+        fs::write(dir.join(IGNORE_FILE), "# local rules\nvendor/\n").unwrap();
+        let discovery = Discovery {
+            max_depth: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(
+            discover(&discovery),
+            [".config/dots", "bin/tool", "node_modules/pkg"].map(PathBuf::from)
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_find_git_repositories_cached() {
+        let dir = temp_dir("list-cached");
+        let (root, cache_dir) = (dir.join("root"), dir.join("cache"));
+        let a = root.join("a");
+        init_source_repo(&a);
+        init_source_repo(&root.join("team").join("b"));
+        fs::create_dir(a.join("docs")).unwrap();
+        fs::write(a.join("docs").join("guide"), "guide\n").unwrap();
+        git(&a, &["add", "docs"]);
+        let discover_with = |refresh: bool, fetch: Option<Duration>| -> Vec<RepoStatus> {
+            let root = root.to_str().unwrap();
+            find_git_repositories_cached(root, &Discovery::default(), fetch, &cache_dir, refresh, &mut |_| {}).unwrap()
+        };
+        let discover = |refresh: bool| discover_with(refresh, None);
+        let changes = |repo: &RepoStatus| (repo.changes.staged, repo.changes.unstaged, repo.changes.untracked);
+
+        assert_eq!(discover(false).len(), 2);
+        assert_eq!(changes(&discover(false)[0]), (1, 0, 0));
+
+        // Working-tree changes are counted again even when the rest of the status is reused
+        fs::write(a.join("README"), "edited\n").unwrap();
+        assert_eq!(changes(&discover(false)[0]), (1, 1, 0));
+        fs::write(a.join("docs").join("NEW"), "new\n").unwrap();
+        assert_eq!(changes(&discover(false)[0]), (1, 1, 1));
+        fs::write(a.join("NEW"), "new\n").unwrap();
+        init_source_repo(&root.join("team").join("c"));
+        let repos = discover(false);
+        assert_eq!(repos.len(), 3);
+        assert_eq!(changes(&repos[0]), (1, 1, 2));
+
+        fs::remove_dir_all(root.join("team").join("b")).unwrap();
+        let paths: Vec<_> = discover(false).into_iter().map(|r| r.path).collect();
+        assert_eq!(paths, [a.clone(), root.join("team").join("c")]);
+
+        // A repository that fails to evaluate comes back once it can be evaluated again
+        let config_path = root.join("team").join("c").join(".git").join("config");
+        let config = fs::read_to_string(&config_path).unwrap();
+        fs::write(&config_path, "[broken").unwrap();
+        assert_eq!(discover(false).len(), 1);
+        fs::write(&config_path, &config).unwrap();
+        assert_eq!(discover(false).len(), 2);
+
+        // The error of an earlier fetch isn't reported by a run that doesn't fetch
+        append_config(&a, "[remote \"broken\"]\n\turl = /nonexistent/repo\n");
+        assert!(discover_with(false, Some(Duration::from_secs(30)))[0].fetch_error.is_some());
+        assert!(discover(false)[0].fetch_error.is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_discover_worktrees_and_bare_repositories() {
+        let dir = temp_dir("list-kinds");
+        let main = dir.join("main");
+        init_source_repo(&main);
+        git(&main, &["worktree", "add", "--quiet", "../wt", "feature"]);
+        gix::init_bare(dir.join("mirror.git")).unwrap();
+
+        let repos = find_git_repositories(dir.to_str().unwrap(), &Discovery::default(), None).unwrap();
+        let kinds: Vec<_> = repos.iter().map(|r| r.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            [
+                RepoKind::Main,
+                RepoKind::Bare,
+                RepoKind::Worktree {
+                    main: fs::canonicalize(&main).unwrap()
+                },
+            ]
+        );
+        assert_eq!(repos[2].current_branch, "feature");
+
+        let tree = build_tree_structure(&repos, dir.to_str().unwrap());
+        assert_eq!(tree.children.len(), 2);
+        let worktree = &tree.children["main"].children["wt"];
+        assert_eq!(worktree.label.as_deref(), Some("worktree"));
+        assert_eq!(tree.children["mirror.git"].label.as_deref(), Some("bare"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let dir = temp_dir("list-symlinks");
+        let (root, outside) = (dir.join("root"), dir.join("outside"));
+        init_source_repo(&root.join("real").join("app"));
+        init_source_repo(&outside.join("lib"));
+        symlink(root.join("real"), root.join("alias")).unwrap();
+        symlink(&outside, root.join("external")).unwrap();
+        symlink(&root, root.join("real").join("loop")).unwrap();
+        symlink(&root, dir.join("root-link")).unwrap();
+
+        let discover = |base: &Path, follow_symlinks: bool| -> Vec<RepoStatus> {
+            let discovery = Discovery {
+                follow_symlinks,
+                ..Default::default()
+            };
+            find_git_repositories(base.to_str().unwrap(), &discovery, None).unwrap()
+        };
+        let paths = |repos: &[RepoStatus], base: &Path| -> Vec<PathBuf> {
+            repos.iter().map(|r| r.path.strip_prefix(base).unwrap().to_path_buf()).collect()
+        };
+
+        assert_eq!(paths(&discover(&root, false), &root), [PathBuf::from("real/app")]);
+        let repos = discover(&root, true);
+        assert_eq!(paths(&repos, &root), ["external/lib", "real/app"].map(PathBuf::from));
+
+        // A root given through a symlink, or with `.` and `..` components, still renders every
+        // repository; `..` leads to the parent of the symlink's target
+        let via_link = dir.join("root-link");
+        let repos = discover(&via_link.join("real").join("loop").join("real").join(".."), false);
+        assert_eq!(paths(&repos, &fs::canonicalize(&root).unwrap()), [PathBuf::from("real/app")]);
+        let tree = build_tree_structure(&discover(&root, true), via_link.to_str().unwrap());
+        assert_eq!(tree.children.len(), 2);
+        assert!(tree.children["real"].children["app"].repo_status.is_some());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_nested_repositories_and_submodules() {
+        let dir = temp_dir("list-nested");
+        let (lib, root) = (dir.join("lib"), dir.join("root"));
+        let app = root.join("app");
+        init_source_repo(&lib);
+        init_source_repo(&app);
+        init_source_repo(&app.join("vendor").join("tool"));
+        for name in ["libs/one", "libs/two"] {
+            git(&app, &["submodule", "add", "--quiet", lib.to_str().unwrap(), name]);
+        }
+        git(&app, &["commit", "--quiet", "-m", "add submodules"]);
+        git(&app.join("libs/one"), &["commit", "--quiet", "--allow-empty", "-m", "local"]);
+        fs::write(app.join("libs/one/NEW"), "new\n").unwrap();
+        git(&app, &["submodule", "deinit", "--quiet", "--force", "libs/two"]);
+
+        let discover = |discovery: &Discovery| find_git_repositories(root.to_str().unwrap(), discovery, None).unwrap();
+        let paths = |repos: &[RepoStatus]| -> Vec<PathBuf> {
+            repos.iter().map(|r| r.path.strip_prefix(&root).unwrap().to_path_buf()).collect()
+        };
+
+        assert_eq!(paths(&discover(&Discovery::default())), [PathBuf::from("app")]);
+
+        let repos = discover(&Discovery {
+            nested: true,
+            ..Default::default()
+        });
+        assert_eq!(paths(&repos), ["app", "app/libs/one", "app/vendor/tool"].map(PathBuf::from));
+        assert_eq!(repos[1].kind, RepoKind::Submodule);
+
+        let repos = discover(&Discovery {
+            nested: true,
+            submodules: true,
+            ..Default::default()
+        });
+        assert_eq!(paths(&repos), ["app", "app/vendor/tool"].map(PathBuf::from));
+        assert!(is_submodule_checkout(&app.join("libs/one"), &root));
+        assert!(!is_submodule_checkout(&app.join("vendor/tool"), &root));
+        assert_eq!(
+            repos[0].submodules,
+            [
+                SubmoduleStatus {
+                    path: PathBuf::from("libs/one"),
+                    initialized: true,
+                    out_of_date: true,
+                    dirty: true,
+                },
+                SubmoduleStatus {
+                    path: PathBuf::from("libs/two"),
+                    initialized: false,
+                    out_of_date: false,
+                    dirty: false,
+                },
+            ]
+        );
+        assert!(Filter { only_problems: true, ..Default::default() }.matches(&repos[0]));
+
+        let tree = build_tree_structure(&repos, root.to_str().unwrap());
+        let app_node = &tree.children["app"];
+        assert_eq!(app_node.children["libs"].children["one"].label.as_deref(), Some("submodule"));
+        assert!(app_node.children["vendor"].children["tool"].repo_status.is_some());
+
+        // A broken `.gitmodules` is reported on the superproject instead of hiding it
+        let gitmodules = fs::read_to_string(app.join(".gitmodules")).unwrap();
+        fs::write(app.join(".gitmodules"), format!("{}[submodule \"broken\"]\n\turl = nowhere\n", gitmodules)).unwrap();
+        let repos = discover(&Discovery {
+            submodules: true,
+            ..Default::default()
+        });
+        assert_eq!(paths(&repos), [PathBuf::from("app")]);
+        assert!(repos[0].submodule_error.is_some());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_dump_entry() {
+        let dir = temp_dir("list-dump");
+        let (source, root) = (dir.join("source"), dir.join("root"));
+        let head = init_source_repo(&source);
+        let clone_path = root.join("team").join("app");
+        clone_repo(&source, &clone_path);
+        append_config(
+            &clone_path,
+            "[remote \"upstream\"]\n\turl = https://example.com/app.git\n\tfetch = +refs/heads/*:refs/remotes/upstream/*\n",
+        );
+
+        let status = load_repo_status(&clone_path, None).unwrap();
+        let entry = dump_entry(&status, root.to_str().unwrap()).unwrap();
+        assert_eq!(entry.path.as_deref(), Some("team/app"));
+        assert_eq!(entry.branch.as_deref(), Some(status.current_branch.as_str()));
+        assert_eq!(entry.commit, Some(head.to_string()));
+        assert_eq!(entry.depth, None);
+        assert!(!entry.single_branch && entry.sparse.is_empty() && !entry.submodules);
+        assert_eq!(
+            entry.remotes,
+            BTreeMap::from([("upstream".to_string(), "https://example.com/app.git".to_string())])
+        );
+
+        // A submodule cloned with its own `.git` directory, as `git get` does, counts as well
+        clone_repo(&source, &clone_path.join("lib"));
+        git(&clone_path, &["submodule", "add", "--quiet", source.to_str().unwrap(), "lib"]);
+        assert!(!clone_path.join(".git/modules").exists());
+        assert!(dump_entry(&status, root.to_str().unwrap()).unwrap().submodules);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_count_changes() {
+        let dir = temp_dir("list-changes");
+        init_source_repo(&dir);
+        let repo = gix::open(&dir).unwrap();
+        assert_eq!(count_changes(&repo).unwrap(), ChangeCounts::default());
+
+        fs::write(dir.join("README"), "changed\n").unwrap();
+        fs::write(dir.join("new.txt"), "new\n").unwrap();
+        fs::write(dir.join(".gitignore"), "*.log\n").unwrap();
+        fs::write(dir.join("debug.log"), "ignored\n").unwrap();
+
+        let counts = count_changes(&repo).unwrap();
+        assert_eq!(counts.unstaged, 1);
+        assert_eq!(counts.untracked, 2);
+        assert_eq!(counts.staged, 0);
+        assert_eq!(counts.conflicted, 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_branch_upstream_status() {
+        let dir = temp_dir("list-upstream");
+        let base = init_source_repo(&dir);
+        let branch = extract_branch_name(&gix::open(&dir).unwrap()).unwrap();
+        append_config(
+            &dir,
+            &format!(
+                "[remote \"origin\"]\n\turl = https://example.com/a/b.git\n\tfetch = +refs/heads/*:refs/remotes/origin/*\n\
+                 [branch \"{branch}\"]\n\tremote = origin\n\tmerge = refs/heads/{branch}\n"
+            ),
+        );
+        let repo = gix::open(&dir).unwrap();
+        let tree = repo.head_commit().unwrap().tree_id().unwrap().detach();
+        let local_ref = format!("refs/heads/{branch}");
+        let upstream_ref = format!("refs/remotes/origin/{branch}");
+        let status_of = |repo: &gix::Repository, name: &str| {
+            let mut reference = repo.find_reference(name).unwrap();
+            get_branch_status(repo, &mut reference).unwrap()
+        };
+
+        repo.reference(upstream_ref.as_str(), base, gix::refs::transaction::PreviousValue::Any, "test")
+            .unwrap();
+        assert!(matches!(status_of(&repo, &local_ref), BranchStatus::Ok));
+        assert!(matches!(status_of(&repo, "refs/heads/feature"), BranchStatus::NoUpstream));
+
+        repo.commit(upstream_ref.as_str(), "upstream", tree, [base]).unwrap();
+        assert!(matches!(status_of(&repo, &local_ref), BranchStatus::Behind { behind: 1 }));
+
+        repo.commit("HEAD", "local", tree, [base]).unwrap();
+        repo.commit("HEAD", "local 2", tree, [repo.head_id().unwrap().detach()]).unwrap();
+        assert!(matches!(
+            status_of(&repo, &local_ref),
+            BranchStatus::Diverged { ahead: 2, behind: 1 }
+        ));
+
+        repo.find_reference(upstream_ref.as_str()).unwrap().delete().unwrap();
+        assert!(matches!(status_of(&repo, &local_ref), BranchStatus::UpstreamGone));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_repo_status_json() {
+        let status = RepoStatus {
+            path: PathBuf::from("/repos/a"),
+            kind: RepoKind::Main,
+            current_branch: "main".to_string(),
+            all_branches: vec![
+                BranchInfo {
+                    name: "main".to_string(),
+                    status: BranchStatus::Diverged { ahead: 1, behind: 2 },
+                },
+                BranchInfo {
+                    name: "feature".to_string(),
+                    status: BranchStatus::Ok,
+                },
+            ],
+            changes: ChangeCounts {
+                untracked: 3,
+                ..Default::default()
+            },
+            remotes: vec![RemoteInfo {
+                name: "origin".to_string(),
+                url: Some("git@github.com:a/b.git".to_string()),
+            }],
+            fetch_error: None,
+            submodules: Vec::new(),
+            submodule_error: None,
+        };
+
+        assert_eq!(
+            serde_json::to_string(&status).unwrap(),
+            concat!(
+                r#"{"path":"/repos/a","kind":"main","current_branch":"main","#,
+                r#""branches":[{"name":"main","status":{"state":"diverged","ahead":1,"behind":2}},"#,
+                r#"{"name":"feature","status":{"state":"ok"}}],"#,
+                r#""changes":{"staged":0,"unstaged":0,"conflicted":0,"untracked":3},"#,
+                r#""remotes":[{"name":"origin","url":"git@github.com:a/b.git"}]}"#,
+            )
+        );
+    }
+
+    #[test]
+    fn test_fetch_remotes() {
+        let dir = temp_dir("list-fetch");
+        let (upstream, local) = (dir.join("upstream"), dir.join("local"));
+        init_source_repo(&upstream);
+        init_source_repo(&local);
+        append_config(
+            &local,
+            &format!(
+                "[remote \"origin\"]\n\turl = {}\n\tfetch = +refs/heads/*:refs/remotes/origin/*\n",
+                upstream.display()
+            ),
+        );
+
+        fetch_remotes(&local, Duration::from_secs(30)).unwrap();
+        let repo = gix::open(&local).unwrap();
+        assert!(repo.try_find_reference("refs/remotes/origin/feature").unwrap().is_some());
+
+        // A failing remote doesn't keep the others from being fetched
+        append_config(&local, "[remote \"broken\"]\n\turl = /nonexistent/repo\n");
+        let upstream_repo = gix::open(&upstream).unwrap();
+        upstream_repo
+            .reference(
+                "refs/heads/new",
+                upstream_repo.head_id().unwrap(),
+                gix::refs::transaction::PreviousValue::Any,
+                "create new",
+            )
+            .unwrap();
+        let err = fetch_remotes(&local, Duration::from_secs(30)).unwrap_err();
+        assert!(format!("{:#}", err).starts_with("Failed to fetch broken"));
+        let repo = gix::open(&local).unwrap();
+        assert!(repo.try_find_reference("refs/remotes/origin/new").unwrap().is_some());
+
+        let err = fetch_remotes(&local, Duration::ZERO).unwrap_err();
+        assert!(err.to_string().starts_with("Fetch timed out after 0s"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn repo_with(status: BranchStatus, changes: ChangeCounts) -> RepoStatus {
+        RepoStatus {
+            path: PathBuf::from("/repos/a"),
+            kind: RepoKind::Main,
+            current_branch: "main".to_string(),
+            all_branches: vec![BranchInfo {
+                name: "main".to_string(),
+                status,
+            }],
+            changes,
+            remotes: Vec::new(),
+            fetch_error: None,
+            submodules: Vec::new(),
+            submodule_error: None,
         }
     }
+
+    #[test]
+    fn test_filter_matches() {
+        let unstaged = ChangeCounts {
+            unstaged: 1,
+            ..Default::default()
+        };
+        let clean = repo_with(BranchStatus::Ok, ChangeCounts::default());
+        let dirty = repo_with(BranchStatus::Ok, unstaged);
+        let diverged = repo_with(BranchStatus::Diverged { ahead: 1, behind: 2 }, ChangeCounts::default());
+        let gone = repo_with(BranchStatus::UpstreamGone, ChangeCounts::default());
+
+        let none = Filter::default();
+        assert!(none.matches(&clean) && none.matches(&dirty));
+
+        let dirty_only = Filter { dirty: true, ..Default::default() };
+        assert!(!dirty_only.matches(&clean));
+        assert!(dirty_only.matches(&dirty));
+        assert!(!dirty_only.matches(&diverged));
+
+        let ahead_or_no_upstream = Filter { ahead: true, no_upstream: true, ..Default::default() };
+        assert!(ahead_or_no_upstream.matches(&diverged));
+        assert!(ahead_or_no_upstream.matches(&gone));
+        assert!(!ahead_or_no_upstream.matches(&dirty));
+
+        let problems = Filter { only_problems: true, ..Default::default() };
+        assert!(!problems.matches(&clean));
+        assert!(problems.matches(&dirty) && problems.matches(&diverged) && problems.matches(&gone));
+    }
+
+    #[test]
+    fn test_color_choice() {
+        assert_eq!(color_choice("always").unwrap(), ColorChoice::Always);
+        assert_eq!(color_choice("never").unwrap(), ColorChoice::Never);
+        assert!(color_choice("sometimes").is_err());
+    }
 }
@@ -1,2 +1,3 @@
 pub mod get;
-pub mod list;
\ No newline at end of file
+pub mod list;
+pub mod sync;
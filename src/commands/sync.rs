@@ -0,0 +1,353 @@
+use crate::commands::list::{self, BranchStatus, RepoKind, RepoStatus};
+use crate::normalize_path;
+use crate::worktree::update_worktree;
+use anyhow::{Context, Result};
+use gix::bstr::ByteSlice;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+pub struct Config {
+    pub base_dir: String,
+    pub fetch_timeout: Duration,
+    pub discovery: list::Discovery,
+}
+
+enum BranchAction {
+    FastForwarded { branch: String, commits: usize },
+    Skipped { branch: String, reason: &'static str },
+}
+
+pub fn execute(config: &Config) -> Result<()> {
+    let repos = list::find_git_repositories(&config.base_dir, &config.discovery, Some(config.fetch_timeout))?;
+    let base_dir = normalize_path(Path::new(&config.base_dir));
+    let mut failed = 0;
+
+    for repo in &repos {
+        let name = list::relative_path(&repo.path, &base_dir).unwrap_or_else(|| repo.path.clone());
+        let name = name.display();
+
+        if let Some(ref error) = repo.fetch_error {
+            failed += 1;
+            eprintln!("✗ {}: fetch failed: {}", name, error);
+            continue;
+        }
+
+        match sync_repository(repo) {
+            Ok(actions) if actions.is_empty() => println!("- {}: up to date", name),
+            Ok(actions) => println!("✓ {}: {}", name, describe_actions(&actions)),
+            Err(e) => {
+                failed += 1;
+                eprintln!("✗ {}: {:#}", name, e);
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(anyhow::anyhow!(
+            "{} of {} repositories failed to sync",
+            failed,
+            repos.len()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Fetch the repository at `path` and fast-forward its branches, returning a description of
+/// what changed.
+pub fn update_repository(path: &Path, fetch_timeout: Duration) -> Result<String> {
+    let status = list::load_repo_status(path, Some(fetch_timeout))?;
+    if let Some(ref error) = status.fetch_error {
+        return Err(anyhow::anyhow!("fetch failed: {}", error));
+    }
+
+    let actions = sync_repository(&status)?;
+    if actions.is_empty() {
+        Ok("up to date".to_string())
+    } else {
+        Ok(describe_actions(&actions))
+    }
+}
+
+fn describe_actions(actions: &[BranchAction]) -> String {
+    actions
+        .iter()
+        .map(|action| match action {
+            BranchAction::FastForwarded { branch, commits } => {
+                format!("{} fast-forwarded {} commit(s)", branch, commits)
+            }
+            BranchAction::Skipped { branch, reason } => format!("{} skipped ({})", branch, reason),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Fast-forward every branch of `status` that is behind its upstream. The checked-out branch is
+/// only updated when the working tree has no uncommitted changes, and branches checked out in
+/// another worktree are left for that worktree.
+fn sync_repository(status: &RepoStatus) -> Result<Vec<BranchAction>> {
+    let mut repo = gix::open(&status.path)?;
+    repo.committer_or_set_generic_fallback()?;
+    let changes = &status.changes;
+    let dirty = changes.staged + changes.unstaged + changes.conflicted > 0;
+
+    let elsewhere = checked_out_elsewhere(&repo);
+
+    let mut actions = Vec::new();
+    for branch in &status.all_branches {
+        let is_current = branch.name == status.current_branch && status.kind != RepoKind::Bare;
+
+        match branch.status {
+            BranchStatus::Behind { .. } if is_current && dirty => actions.push(BranchAction::Skipped {
+                branch: branch.name.clone(),
+                reason: "uncommitted changes",
+            }),
+            BranchStatus::Behind { .. } if !is_current && elsewhere.contains(&branch.name) => {
+                actions.push(BranchAction::Skipped {
+                    branch: branch.name.clone(),
+                    reason: "checked out in another worktree",
+                })
+            }
+            BranchStatus::Behind { behind: commits } => {
+                fast_forward_branch(&repo, &branch.name, is_current)
+                    .with_context(|| format!("Failed to fast-forward {}", branch.name))?;
+                actions.push(BranchAction::FastForwarded {
+                    branch: branch.name.clone(),
+                    commits,
+                });
+            }
+            BranchStatus::Diverged { .. } => actions.push(BranchAction::Skipped {
+                branch: branch.name.clone(),
+                reason: "diverged",
+            }),
+            _ => {}
+        }
+    }
+
+    Ok(actions)
+}
+
+/// Branches checked out in any worktree of the repository other than `repo`'s own.
+fn checked_out_elsewhere(repo: &gix::Repository) -> HashSet<String> {
+    let Ok(main) = repo.main_repo() else {
+        return HashSet::new();
+    };
+    let mut worktrees: Vec<gix::Repository> = main
+        .worktrees()
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|proxy| proxy.into_repo_with_possibly_inaccessible_worktree().ok())
+        .collect();
+    if main.workdir().is_some() {
+        worktrees.push(main.clone());
+    }
+
+    let own_git_dir = fs::canonicalize(repo.git_dir()).ok();
+    worktrees
+        .iter()
+        .filter(|other| fs::canonicalize(other.git_dir()).ok() != own_git_dir)
+        .filter_map(|other| other.head_name().ok().flatten())
+        .filter_map(|name| name.as_bstr().to_str().ok()?.strip_prefix("refs/heads/").map(str::to_string))
+        .collect()
+}
+
+fn fast_forward_branch(repo: &gix::Repository, branch: &str, is_current: bool) -> Result<()> {
+    let ref_name = format!("refs/heads/{}", branch);
+    let mut local = repo.find_reference(ref_name.as_str())?;
+    let tracking_name = repo
+        .branch_remote_tracking_ref_name(local.name(), gix::remote::Direction::Fetch)
+        .context("Branch has no upstream")??;
+    let old_id = local.peel_to_id_in_place()?.detach();
+    let new_id = repo.find_reference(tracking_name.as_ref())?.peel_to_id_in_place()?.detach();
+
+    if is_current {
+        update_worktree(repo, new_id)?;
+    }
+
+    repo.reference(
+        ref_name.as_str(),
+        new_id,
+        gix::refs::transaction::PreviousValue::MustExistAndMatch(old_id.into()),
+        "sync: fast-forward",
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{clone_repo, git, init_source_repo, temp_dir, write_tree};
+
+    /// Commit a tree holding exactly `files`, which may be in subdirectories, on top of HEAD.
+    fn commit_files(repo: &gix::Repository, files: &[(&str, &str)]) -> gix::ObjectId {
+        let tree = write_tree(repo, files);
+        let parent = repo.head_id().unwrap().detach();
+        repo.commit("HEAD", "update", tree, [parent]).unwrap().detach()
+    }
+
+    fn sync_root(root: &Path) {
+        let config = Config {
+            base_dir: root.to_str().unwrap().to_string(),
+            fetch_timeout: Duration::from_secs(30),
+            discovery: Default::default(),
+        };
+        execute(&config).unwrap();
+    }
+
+    fn assert_clean(path: &Path) {
+        let status = list::load_repo_status(path, None).unwrap();
+        assert!(status.changes.is_empty(), "{:?}", status.changes);
+    }
+
+    #[test]
+    fn test_sync_fast_forwards_current_branch() {
+        let dir = temp_dir("sync-ff");
+        let (upstream_path, root) = (dir.join("upstream"), dir.join("root"));
+        init_source_repo(&upstream_path);
+        let local_path = root.join("local");
+        clone_repo(&upstream_path, &local_path);
+
+        let upstream = gix::open(&upstream_path).unwrap();
+        let new_head = commit_files(&upstream, &[("README", "updated\n"), ("NEW", "new\n")]);
+        sync_root(&root);
+
+        let local = gix::open(&local_path).unwrap();
+        assert_eq!(local.head_id().unwrap().detach(), new_head);
+        assert_eq!(fs::read_to_string(local_path.join("README")).unwrap(), "updated\n");
+        assert_eq!(fs::read_to_string(local_path.join("NEW")).unwrap(), "new\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sync_applies_attributes() {
+        let dir = temp_dir("sync-attributes");
+        let (upstream_path, root) = (dir.join("upstream"), dir.join("root"));
+        init_source_repo(&upstream_path);
+        let local_path = root.join("local");
+        clone_repo(&upstream_path, &local_path);
+
+        let upstream = gix::open(&upstream_path).unwrap();
+        commit_files(
+            &upstream,
+            &[("README", "hello\n"), (".gitattributes", "*.txt text eol=crlf\n"), ("notes.txt", "one\ntwo\n")],
+        );
+        sync_root(&root);
+
+        assert_eq!(fs::read_to_string(local_path.join("notes.txt")).unwrap(), "one\r\ntwo\r\n");
+        assert_clean(&local_path);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sync_swaps_files_and_directories() {
+        let dir = temp_dir("sync-dir-file");
+        let (upstream_path, root) = (dir.join("upstream"), dir.join("root"));
+        init_source_repo(&upstream_path);
+        let upstream = gix::open(&upstream_path).unwrap();
+        commit_files(&upstream, &[("README", "hello\n"), ("a/b", "b\n"), ("c", "c\n")]);
+        let local_path = root.join("local");
+        clone_repo(&upstream_path, &local_path);
+        let readme_stat = |path: &Path| {
+            let index = gix::open(path).unwrap().open_index().unwrap();
+            index.entry_by_path("README".into()).unwrap().stat
+        };
+        let stat = readme_stat(&local_path);
+
+        commit_files(&upstream, &[("README", "hello\n"), ("a", "a\n"), ("c/d", "d\n")]);
+        sync_root(&root);
+
+        assert_eq!(fs::read_to_string(local_path.join("a")).unwrap(), "a\n");
+        assert_eq!(fs::read_to_string(local_path.join("c/d")).unwrap(), "d\n");
+        assert_eq!(readme_stat(&local_path), stat);
+        assert_clean(&local_path);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sync_keeps_untracked_files() {
+        let dir = temp_dir("sync-untracked");
+        let (upstream_path, root) = (dir.join("upstream"), dir.join("root"));
+        let base = init_source_repo(&upstream_path);
+        let local_path = root.join("local");
+        clone_repo(&upstream_path, &local_path);
+        fs::create_dir(local_path.join("a")).unwrap();
+        fs::write(local_path.join("a/untracked"), "mine\n").unwrap();
+
+        let upstream = gix::open(&upstream_path).unwrap();
+        let new_head = commit_files(&upstream, &[("README", "hello\n"), ("a", "a\n")]);
+        let local = gix::open(&local_path).unwrap();
+        assert!(update_worktree(&local, new_head).is_err());
+
+        assert_eq!(local.head_id().unwrap().detach(), base);
+        assert_eq!(fs::read_to_string(local_path.join("a/untracked")).unwrap(), "mine\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sync_skips_dirty_current_branch() {
+        let dir = temp_dir("sync-dirty");
+        let (upstream_path, root) = (dir.join("upstream"), dir.join("root"));
+        let base = init_source_repo(&upstream_path);
+        let local_path = root.join("local");
+        clone_repo(&upstream_path, &local_path);
+        fs::write(local_path.join("README"), "local edit\n").unwrap();
+
+        let upstream = gix::open(&upstream_path).unwrap();
+        commit_files(&upstream, &[("README", "updated\n")]);
+
+        let repos =
+            list::find_git_repositories(root.to_str().unwrap(), &Default::default(), Some(Duration::from_secs(30)))
+                .unwrap();
+        let actions = sync_repository(&repos[0]).unwrap();
+
+        assert!(matches!(
+            actions.as_slice(),
+            [BranchAction::Skipped { reason: "uncommitted changes", .. }]
+        ));
+        let local = gix::open(&local_path).unwrap();
+        assert_eq!(local.head_id().unwrap().detach(), base);
+        assert_eq!(fs::read_to_string(local_path.join("README")).unwrap(), "local edit\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sync_leaves_branches_of_other_worktrees() {
+        let dir = temp_dir("sync-worktree");
+        let (upstream_path, root) = (dir.join("upstream"), dir.join("root"));
+        let base = init_source_repo(&upstream_path);
+        let local_path = root.join("local");
+        clone_repo(&upstream_path, &local_path);
+        git(&local_path, &["worktree", "add", "--quiet", "../wt", "feature"]);
+
+        let upstream = gix::open(&upstream_path).unwrap();
+        let tree = upstream.find_commit(base).unwrap().tree_id().unwrap().detach();
+        let new_feature = upstream
+            .commit("refs/heads/feature", "feature work", tree, [base])
+            .unwrap()
+            .detach();
+
+        let repos =
+            list::find_git_repositories(root.to_str().unwrap(), &Default::default(), Some(Duration::from_secs(30)))
+                .unwrap();
+        assert!(matches!(
+            sync_repository(&repos[0]).unwrap().as_slice(),
+            [BranchAction::Skipped { reason: "checked out in another worktree", .. }]
+        ));
+        assert!(matches!(
+            sync_repository(&repos[1]).unwrap().as_slice(),
+            [BranchAction::FastForwarded { commits: 1, .. }]
+        ));
+
+        let worktree = gix::open(root.join("wt")).unwrap();
+        assert_eq!(worktree.head_id().unwrap().detach(), new_feature);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,252 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Settings shared by all git-extend binaries.
+///
+/// Values are layered from lowest to highest precedence: built-in defaults, the TOML config
+/// file, `gitextend.*` keys in the global git config, then environment variables. Command-line
+/// arguments are applied on top by each binary.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub root: Option<String>,
+    pub host: String,
+    pub scheme: String,
+    pub skip_host: bool,
+    pub jobs: usize,
+    pub output: String,
+    pub color: String,
+    /// URL prefixes such as `gh` in `gh:owner/repo`, mapped to what they expand to.
+    pub aliases: BTreeMap<String, String>,
+    /// `url.<base>.insteadOf` rules from git config, as `(prefix, base)` pairs.
+    pub url_rewrites: Vec<(String, String)>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            root: None,
+            host: "github.com".to_string(),
+            scheme: "ssh".to_string(),
+            skip_host: false,
+            jobs: 4,
+            output: "tree".to_string(),
+            color: "auto".to_string(),
+            aliases: [("gh", "github.com"), ("gl", "gitlab.com"), ("bb", "bitbucket.org")]
+                .into_iter()
+                .map(|(alias, target)| (alias.to_string(), target.to_string()))
+                .collect(),
+            url_rewrites: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+struct FileSettings {
+    root: Option<String>,
+    host: Option<String>,
+    scheme: Option<String>,
+    skip_host: Option<bool>,
+    jobs: Option<usize>,
+    output: Option<String>,
+    color: Option<String>,
+    aliases: BTreeMap<String, String>,
+}
+
+impl Settings {
+    /// Load settings from the config file, global git config and environment.
+    pub fn load() -> Result<Settings> {
+        let mut settings = Settings::default();
+
+        if let Some(path) = config_file_path()
+            && path.exists()
+        {
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read config file {}", path.display()))?;
+            settings.apply_file(&content)
+                .with_context(|| format!("Invalid config file {}", path.display()))?;
+        }
+
+        if let Ok(git_config) = gix::config::File::from_globals() {
+            settings.apply_git_config(&git_config)?;
+        }
+
+        settings.apply_env(|key| env::var(key).ok())?;
+        Ok(settings)
+    }
+
+    fn apply_file(&mut self, content: &str) -> Result<()> {
+        let file: FileSettings = toml::from_str(content)?;
+
+        self.root = file.root.or(self.root.take());
+        self.host = file.host.unwrap_or(std::mem::take(&mut self.host));
+        self.scheme = file.scheme.unwrap_or(std::mem::take(&mut self.scheme));
+        self.skip_host = file.skip_host.unwrap_or(self.skip_host);
+        self.jobs = file.jobs.unwrap_or(self.jobs);
+        self.output = file.output.unwrap_or(std::mem::take(&mut self.output));
+        self.color = file.color.unwrap_or(std::mem::take(&mut self.color));
+        self.aliases.extend(file.aliases);
+        Ok(())
+    }
+
+    fn apply_git_config(&mut self, config: &gix::config::File<'_>) -> Result<()> {
+        let string = |key: &str| config.string(key).map(|v| v.to_string());
+
+        if let Some(root) = string("gitextend.root") {
+            self.root = Some(root);
+        }
+        if let Some(host) = string("gitextend.host") {
+            self.host = host;
+        }
+        if let Some(scheme) = string("gitextend.scheme") {
+            self.scheme = scheme;
+        }
+        if let Some(skip_host) = config.boolean("gitextend.skipHost") {
+            self.skip_host = skip_host.context("Invalid gitextend.skipHost")?;
+        }
+        if let Some(jobs) = config.integer("gitextend.jobs") {
+            self.jobs = usize::try_from(jobs.context("Invalid gitextend.jobs")?)
+                .context("Invalid gitextend.jobs")?;
+        }
+        if let Some(output) = string("gitextend.output") {
+            self.output = output;
+        }
+        if let Some(color) = string("gitextend.color") {
+            self.color = color;
+        }
+
+        for section in config.sections_by_name("gitextend").into_iter().flatten() {
+            if section.header().subsection_name().is_some_and(|name| name == "alias") {
+                for alias in section.value_names() {
+                    if let Some(target) = section.value(alias) {
+                        self.aliases.insert(alias.to_string(), target.to_string());
+                    }
+                }
+            }
+        }
+
+        for section in config.sections_by_name("url").into_iter().flatten() {
+            let Some(base) = section.header().subsection_name() else { continue };
+            for prefix in section.values("insteadOf") {
+                self.url_rewrites.push((prefix.to_string(), base.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<()> {
+        if let Some(root) = var("GIT_PATH") {
+            self.root = Some(root);
+        }
+        if let Some(host) = var("GIT_EXTEND_HOST") {
+            self.host = host;
+        }
+        if let Some(scheme) = var("GIT_EXTEND_SCHEME") {
+            self.scheme = scheme;
+        }
+        if let Some(skip_host) = var("GIT_EXTEND_SKIP_HOST") {
+            self.skip_host = gix::config::Boolean::try_from(gix::bstr::BStr::new(&skip_host))
+                .context("Invalid GIT_EXTEND_SKIP_HOST")?
+                .0;
+        }
+        if let Some(jobs) = var("GIT_EXTEND_JOBS") {
+            self.jobs = jobs.parse().context("Invalid GIT_EXTEND_JOBS")?;
+        }
+        if let Some(output) = var("GIT_EXTEND_OUTPUT") {
+            self.output = output;
+        }
+        if let Some(color) = var("GIT_EXTEND_COLOR") {
+            self.color = color;
+        }
+        Ok(())
+    }
+}
+
+/// The config file location: `$GIT_EXTEND_CONFIG`, or `git-extend/config.toml` under
+/// `$XDG_CONFIG_HOME` (falling back to `~/.config`).
+fn config_file_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("GIT_EXTEND_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join("git-extend").join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layer_precedence() {
+        let mut settings = Settings::default();
+        settings
+            .apply_file("root = \"/file\"\nhost = \"gitlab.com\"\nskip-host = true\njobs = 2\n")
+            .unwrap();
+        assert_eq!(settings.root.as_deref(), Some("/file"));
+        assert_eq!(settings.host, "gitlab.com");
+        assert_eq!(settings.scheme, "ssh");
+        assert!(settings.skip_host);
+        assert_eq!(settings.jobs, 2);
+
+        let git_config: gix::config::File = "[gitextend]\n\thost = git.example.com\n\tjobs = 8\n"
+            .parse()
+            .unwrap();
+        settings.apply_git_config(&git_config).unwrap();
+        assert_eq!(settings.host, "git.example.com");
+        assert_eq!(settings.jobs, 8);
+        assert_eq!(settings.root.as_deref(), Some("/file"));
+
+        settings
+            .apply_env(|key| (key == "GIT_PATH").then(|| "/env".to_string()))
+            .unwrap();
+        assert_eq!(settings.root.as_deref(), Some("/env"));
+        assert_eq!(settings.host, "git.example.com");
+    }
+
+    #[test]
+    fn test_env_skip_host() {
+        let env = |value: &'static str| move |key: &str| (key == "GIT_EXTEND_SKIP_HOST").then(|| value.to_string());
+
+        let mut settings = Settings::default();
+        settings.apply_env(env("yes")).unwrap();
+        assert!(settings.skip_host);
+        settings.apply_env(env("off")).unwrap();
+        assert!(!settings.skip_host);
+        assert!(settings.apply_env(env("ture")).is_err());
+    }
+
+    #[test]
+    fn test_aliases_and_url_rewrites() {
+        let mut settings = Settings::default();
+        settings.apply_file("[aliases]\nwork = \"git@git.corp.example.com:\"\n").unwrap();
+
+        let git_config: gix::config::File = concat!(
+            "[gitextend \"alias\"]\n\tgh = github.example.com\n",
+            "[url \"https://mirror.example.com/\"]\n\tinsteadOf = https://github.com/\n",
+        )
+        .parse()
+        .unwrap();
+        settings.apply_git_config(&git_config).unwrap();
+
+        assert_eq!(settings.aliases["work"], "git@git.corp.example.com:");
+        assert_eq!(settings.aliases["gh"], "github.example.com");
+        assert_eq!(settings.aliases["gl"], "gitlab.com");
+        assert_eq!(
+            settings.url_rewrites,
+            [("https://github.com/".to_string(), "https://mirror.example.com/".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_unknown_file_key_is_rejected() {
+        assert!(Settings::default().apply_file("hots = \"typo\"\n").is_err());
+    }
+}
@@ -0,0 +1,243 @@
+use crate::clone_options::{CloneOptions, parse_filter, parse_sparse_dir};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::num::NonZeroU32;
+
+/// Version written by [`to_string`]. Files without a `version` key use the plain format of one
+/// `url [branch] [options...]` per line.
+pub const VERSION: u32 = 2;
+
+/// One repository in a dump file.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Entry {
+    /// Fetch URL of `origin`.
+    pub url: String,
+    /// Where to clone, relative to the root. Derived from the URL when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// Commit to leave the checked-out branch at, as a full hex object id.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depth: Option<NonZeroU32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub single_branch: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sparse: Vec<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub submodules: bool,
+    /// Remotes other than `origin`, by name.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub remotes: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Dump {
+    version: u32,
+    #[serde(default, rename = "repository")]
+    repositories: Vec<Entry>,
+}
+
+impl Entry {
+    pub fn clone_options(&self) -> CloneOptions {
+        CloneOptions {
+            depth: self.depth,
+            filter: self.filter.clone(),
+            single_branch: self.single_branch,
+            sparse: self.sparse.clone(),
+            submodules: self.submodules,
+        }
+    }
+
+    fn validate(&mut self) -> Result<()> {
+        if self.url.is_empty() {
+            return Err(anyhow::anyhow!("Missing repository URL"));
+        }
+        if let Some(path) = &self.path {
+            self.path = Some(parse_clone_path(path)?);
+        }
+        if let Some(commit) = &self.commit {
+            gix::ObjectId::from_hex(commit.as_bytes()).with_context(|| format!("Invalid commit '{}'", commit))?;
+        }
+        if let Some(filter) = &self.filter {
+            parse_filter(filter).map_err(anyhow::Error::msg)?;
+        }
+        self.sparse = self
+            .sparse
+            .iter()
+            .map(|dir| parse_sparse_dir(dir))
+            .collect::<Result<_, _>>()
+            .map_err(anyhow::Error::msg)?;
+        Ok(())
+    }
+}
+
+/// Normalize a clone path to a path relative to the root, without a trailing slash.
+fn parse_clone_path(path: &str) -> Result<String> {
+    let relative = path.trim_end_matches('/');
+    let escapes = relative.split('/').any(|c| matches!(c, "" | "." | ".."));
+    if path.starts_with('/') || relative.contains('\\') || escapes {
+        return Err(anyhow::anyhow!(
+            "Invalid path '{}': expected a path relative to the root without '.' or '..'",
+            path
+        ));
+    }
+    Ok(relative.to_string())
+}
+
+/// Parse a dump file in either the versioned or the plain format.
+pub fn parse(content: &str) -> Result<Vec<Entry>> {
+    // Only the versioned format starts with a `version = <n>` key; a plain line is never TOML
+    let versioned = content
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .is_some_and(|line| line.parse::<toml::Table>().is_ok_and(|table| table.contains_key("version")));
+
+    if !versioned {
+        return parse_plain(content);
+    }
+
+    let dump: Dump = toml::from_str(content).context("Invalid dump file")?;
+    if dump.version != VERSION {
+        return Err(anyhow::anyhow!("Unsupported dump file version {}", dump.version));
+    }
+
+    let mut entries = dump.repositories;
+    for (index, entry) in entries.iter_mut().enumerate() {
+        entry
+            .validate()
+            .with_context(|| format!("Invalid dump file repository #{}", index + 1))?;
+    }
+    Ok(entries)
+}
+
+/// Render `entries` in the versioned format.
+pub fn to_string(entries: &[Entry]) -> Result<String> {
+    let dump = Dump {
+        version: VERSION,
+        repositories: entries.to_vec(),
+    };
+    Ok(toml::to_string(&dump)?)
+}
+
+/// Parse the plain format. Each line holds a URL, optionally followed by a branch and any of
+/// `--depth=<n>`, `--filter=<spec>`, `--single-branch`, `--sparse=<dir>[,<dir>...]` and
+/// `--recurse-submodules`.
+fn parse_plain(content: &str) -> Result<Vec<Entry>> {
+    content
+        .lines()
+        .enumerate()
+        .map(|(number, line)| (number, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| {
+            parse_plain_line(line).with_context(|| format!("Invalid dump file entry on line {}", number + 1))
+        })
+        .collect()
+}
+
+fn parse_plain_line(line: &str) -> Result<Entry> {
+    let mut parts = line.split_whitespace();
+    let mut entry = Entry {
+        url: parts.next().context("Missing repository URL")?.to_string(),
+        ..Default::default()
+    };
+
+    for part in parts {
+        match part.split_once('=') {
+            _ if part == "--single-branch" => entry.single_branch = true,
+            _ if part == "--recurse-submodules" => entry.submodules = true,
+            Some(("--depth", depth)) => {
+                entry.depth = Some(depth.parse().with_context(|| format!("Invalid depth '{}'", depth))?)
+            }
+            Some(("--filter", filter)) => entry.filter = Some(filter.to_string()),
+            Some(("--sparse", dirs)) => entry.sparse = dirs.split(',').map(str::to_string).collect(),
+            _ if part.starts_with("--") => return Err(anyhow::anyhow!("Unknown option '{}'", part)),
+            _ if entry.branch.is_none() => entry.branch = Some(part.to_string()),
+            _ => return Err(anyhow::anyhow!("Unexpected '{}'", part)),
+        }
+    }
+
+    entry.validate()?;
+    Ok(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_plain() {
+        let entries = parse("# comment\n\ngithub.com/a/b\n  git@github.com:c/d.git  dev  \n").unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].url, "github.com/a/b");
+        assert_eq!(entries[0].branch, None);
+        assert_eq!(entries[1].url, "git@github.com:c/d.git");
+        assert_eq!(entries[1].branch.as_deref(), Some("dev"));
+    }
+
+    #[test]
+    fn test_parse_plain_clone_options() {
+        let entries =
+            parse("github.com/a/b --depth=1 --filter=blob:none\ngithub.com/c/d main --single-branch --sparse=docs,src/app/\n")
+                .unwrap();
+        assert_eq!(entries[0].branch, None);
+        assert_eq!(entries[0].depth, NonZeroU32::new(1));
+        assert_eq!(entries[0].filter.as_deref(), Some("blob:none"));
+        assert_eq!(entries[1].branch.as_deref(), Some("main"));
+        assert!(entries[1].single_branch);
+        assert_eq!(entries[1].sparse, ["docs", "src/app"]);
+
+        let err = parse("github.com/a/b\ngithub.com/c/d --filter=everything\n").unwrap_err();
+        assert!(format!("{:#}", err).contains("line 2"));
+        assert!(parse("github.com/a/b --shallow\n").is_err());
+    }
+
+    #[test]
+    fn test_versioned_round_trip() {
+        let entries = vec![
+            Entry {
+                url: "git@github.com:a/b.git".to_string(),
+                ..Default::default()
+            },
+            Entry {
+                url: "https://gitlab.com/group/sub/repo.git".to_string(),
+                path: Some("work/repo".to_string()),
+                branch: Some("main".to_string()),
+                commit: Some("0123456789abcdef0123456789abcdef01234567".to_string()),
+                depth: NonZeroU32::new(1),
+                filter: Some("blob:none".to_string()),
+                single_branch: true,
+                sparse: vec!["docs".to_string()],
+                submodules: true,
+                remotes: BTreeMap::from([("upstream".to_string(), "https://example.com/repo.git".to_string())]),
+            },
+        ];
+
+        let content = to_string(&entries).unwrap();
+        assert!(content.starts_with("version = 2\n"));
+        assert_eq!(parse(&content).unwrap(), entries);
+
+        assert!(parse("version = 3\n").is_err());
+        let err = parse("version = 2\n[[repository]]\nurl = \"a/b\"\npath = \"../escape\"\n").unwrap_err();
+        assert!(format!("{:#}", err).contains("Invalid path '../escape'"));
+        assert!(parse("version = 2\n[[repository]]\nurl = \"a/b\"\npath = \"/abs\"\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_plain_url_starting_with_version() {
+        let entries = parse("versioneer/repo main
+https://github.com/version/repo
+").unwrap();
+        assert_eq!(entries[0].url, "versioneer/repo");
+        assert_eq!(entries[0].branch.as_deref(), Some("main"));
+        assert_eq!(entries[1].url, "https://github.com/version/repo");
+    }
+}
@@ -1,12 +1,146 @@
 use anyhow::Result;
+use config::Settings;
 use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
 
 pub mod url_parser;
 pub mod commands;
+pub mod config;
+pub mod dump;
+pub mod cache;
+pub mod clone_options;
+mod worktree;
 
-pub fn get_base_dir(provided_dir: Option<String>) -> Result<String> {
-    match provided_dir {
-        Some(dir) => Ok(dir),
-        None => env::var("GIT_PATH").map_err(|_| anyhow::anyhow!("GIT_PATH environment variable not set"))
+#[cfg(test)]
+mod test_support;
+
+/// Root used when neither the command line, the environment nor the config names one.
+pub const DEFAULT_ROOT: &str = "~/repositories";
+
+/// Resolve the repositories root shared by all binaries: the explicit `provided_dir`, then the
+/// configured root (`GIT_PATH` or the config file), then [`DEFAULT_ROOT`], with `~` and
+/// environment variables expanded.
+pub fn get_base_dir(provided_dir: Option<String>, settings: &Settings) -> Result<String> {
+    let dir = provided_dir
+        .or_else(|| settings.root.clone())
+        .unwrap_or_else(|| DEFAULT_ROOT.to_string());
+    expand_path(&dir, |name| env::var(name).ok())
+}
+
+/// Make `path` absolute and resolve its `.` and `..` components, leaving symlinks in place. The
+/// part before a `..` is canonicalized first when it exists, so that `..` after a symlink leads
+/// to the parent of its target, as it does for the OS.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if let Ok(canonical) = fs::canonicalize(&normalized) {
+                    normalized = canonical;
+                }
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Expand a leading `~` and any `$VAR` or `${VAR}` references in `path`.
+fn expand_path(path: &str, var: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let lookup = |name: &str| {
+        var(name).ok_or_else(|| anyhow::anyhow!("Environment variable {} is not set (in path {})", name, path))
+    };
+
+    let mut expanded = String::with_capacity(path.len());
+    let mut rest = path;
+
+    if rest == "~" || rest.starts_with("~/") {
+        expanded.push_str(&lookup("HOME")?);
+        rest = &rest[1..];
+    }
+
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+
+        let (name, remainder) = if let Some(braced) = after.strip_prefix('{') {
+            let end = braced
+                .find('}')
+                .ok_or_else(|| anyhow::anyhow!("Unterminated variable reference in path {}", path))?;
+            (&braced[..end], &braced[end + 1..])
+        } else {
+            let end = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[..end], &after[end..])
+        };
+
+        if name.is_empty() {
+            expanded.push('$');
+        } else {
+            expanded.push_str(&lookup(name)?);
+        }
+        rest = remainder;
+    }
+
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn var(name: &str) -> Option<String> {
+        match name {
+            "HOME" => Some("/home/me".to_string()),
+            "SRC" => Some("src".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_expand_path() {
+        assert_eq!(expand_path("~", var).unwrap(), "/home/me");
+        assert_eq!(expand_path("~/repositories", var).unwrap(), "/home/me/repositories");
+        assert_eq!(expand_path("$HOME/$SRC", var).unwrap(), "/home/me/src");
+        assert_eq!(expand_path("${HOME}/${SRC}-work", var).unwrap(), "/home/me/src-work");
+        assert_eq!(expand_path("/plain/path~", var).unwrap(), "/plain/path~");
+        assert_eq!(expand_path("/a/$/b", var).unwrap(), "/a/$/b");
+        assert!(expand_path("$MISSING/x", var).is_err());
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(normalize_path(Path::new("/a/./b/../c/")), PathBuf::from("/a/c"));
+        assert_eq!(normalize_path(Path::new("/..")), PathBuf::from("/"));
+        assert_eq!(normalize_path(Path::new("x/../y")), env::current_dir().unwrap().join("y"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_normalize_path_through_symlink() {
+        let dir = fs::canonicalize(test_support::temp_dir("normalize-symlink")).unwrap();
+        fs::create_dir_all(dir.join("real/nested")).unwrap();
+        std::os::unix::fs::symlink(dir.join("real/nested"), dir.join("link")).unwrap();
+
+        assert_eq!(normalize_path(&dir.join("link/../sibling")), dir.join("real/sibling"));
+        assert_eq!(normalize_path(&dir.join("link/./x")), dir.join("link/x"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_get_base_dir_precedence() {
+        let mut settings = Settings::default();
+        assert_eq!(get_base_dir(Some("/cli".to_string()), &settings).unwrap(), "/cli");
+
+        settings.root = Some("/configured".to_string());
+        assert_eq!(get_base_dir(Some("/cli".to_string()), &settings).unwrap(), "/cli");
+        assert_eq!(get_base_dir(None, &settings).unwrap(), "/configured");
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,104 @@
+use gix::objs::tree::EntryKind;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Create an empty scratch directory unique to this test process.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("git-extend-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Initialize a repository at `path` with a single checked-out commit on the default branch
+/// and a `feature` branch pointing at the same commit.
+pub fn init_source_repo(path: &Path) -> gix::ObjectId {
+    gix::init(path).unwrap();
+    append_config(path, "[user]\n\tname = git-extend\n\temail = git-extend@example.com\n");
+    let repo = gix::open(path).unwrap();
+
+    let blob = repo.write_blob(b"hello\n").unwrap().detach();
+    let tree = gix::objs::Tree {
+        entries: vec![gix::objs::tree::Entry {
+            mode: gix::objs::tree::EntryKind::Blob.into(),
+            filename: "README".into(),
+            oid: blob,
+        }],
+    };
+    let tree_id = repo.write_object(&tree).unwrap().detach();
+    let commit = repo
+        .commit("HEAD", "initial", tree_id, gix::commit::NO_PARENT_IDS)
+        .unwrap()
+        .detach();
+    fs::write(path.join("README"), "hello\n").unwrap();
+    let mut index = repo.index_from_tree(&tree_id).unwrap();
+    index.write(Default::default()).unwrap();
+
+    repo.reference(
+        "refs/heads/feature",
+        commit,
+        gix::refs::transaction::PreviousValue::Any,
+        "create feature",
+    )
+    .unwrap();
+    commit
+}
+
+/// Append raw `text` to the repository configuration at `path`.
+pub fn append_config(path: &Path, text: &str) {
+    let config_path = path.join(".git").join("config");
+    let mut config = fs::read_to_string(&config_path).unwrap();
+    config.push_str(text);
+    fs::write(config_path, config).unwrap();
+}
+
+/// Clone `source` into `dest` with a checked-out working tree.
+pub fn clone_repo(source: &Path, dest: &Path) -> gix::Repository {
+    fs::create_dir_all(dest.parent().unwrap()).unwrap();
+    let (mut checkout, _) = gix::prepare_clone(source.to_str().unwrap(), dest)
+        .unwrap()
+        .fetch_then_checkout(gix::progress::Discard, &gix::interrupt::IS_INTERRUPTED)
+        .unwrap();
+    let (repo, _) = checkout
+        .main_worktree(gix::progress::Discard, &gix::interrupt::IS_INTERRUPTED)
+        .unwrap();
+    repo
+}
+
+/// Write a tree holding exactly `files`, which may be in subdirectories, to `repo`.
+pub fn write_tree(repo: &gix::Repository, files: &[(&str, &str)]) -> gix::ObjectId {
+    let mut subdirs: BTreeMap<&str, Vec<(&str, &str)>> = BTreeMap::new();
+    let mut entries = Vec::new();
+    for (path, content) in files {
+        match path.split_once('/') {
+            Some((dir, rest)) => subdirs.entry(dir).or_default().push((rest, content)),
+            None => entries.push(gix::objs::tree::Entry {
+                mode: EntryKind::Blob.into(),
+                filename: (*path).into(),
+                oid: repo.write_blob(content.as_bytes()).unwrap().detach(),
+            }),
+        }
+    }
+    entries.extend(subdirs.iter().map(|(dir, files)| gix::objs::tree::Entry {
+        mode: EntryKind::Tree.into(),
+        filename: (*dir).into(),
+        oid: write_tree(repo, files),
+    }));
+    entries.sort();
+    repo.write_object(gix::objs::Tree { entries }).unwrap().detach()
+}
+
+/// Run git with `args` in `dir`, failing the test with its stderr if it doesn't succeed.
+/// Local file URLs are allowed for submodules, and commits get a fixed identity.
+pub fn git(dir: &Path, args: &[&str]) {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["-c", "protocol.file.allow=always", "-c", "user.name=test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
@@ -1,21 +1,38 @@
 use anyhow::Result;
 use std::path::PathBuf;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct RepoInfo {
     pub host: String,
-    pub owner: String,
+    /// Slash-separated path of the groups containing the repository, e.g. `group/subgroup`.
+    /// Empty when the repository sits directly under the host.
+    pub namespace: String,
     pub name: String,
     pub full_url: String,
+    /// The scheme the URL was given with, or `None` for short forms like `owner/repo`.
+    pub scheme: Option<String>,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    /// Branch or tag named by a forge web URL such as `.../tree/<branch>`, still followed by the
+    /// path within the repository if the URL has one. Branch names can contain slashes, so where
+    /// the ref ends is only known once the remote's refs are listed.
+    pub branch: Option<String>,
+    /// Commit named by a forge web URL such as a permalink `.../blob/<commit>/<file>`.
+    pub commit: Option<String>,
 }
 
 impl RepoInfo {
     pub fn get_clone_path(&self, base_dir: &str) -> PathBuf {
         PathBuf::from(base_dir)
             .join(&self.host)
-            .join(&self.owner)
+            .join(self.namespace_path())
             .join(&self.name)
     }
+
+    /// The namespace as a relative path, one directory per group.
+    pub fn namespace_path(&self) -> PathBuf {
+        self.namespace.split('/').filter(|group| !group.is_empty()).collect()
+    }
 }
 
 pub fn parse_repo_url(url: &str) -> Result<RepoInfo> {
@@ -24,13 +41,28 @@ pub fn parse_repo_url(url: &str) -> Result<RepoInfo> {
     // Handle different URL formats:
     // - https://github.com/owner/repo
     // - https://github.com/owner/repo.git
+    // - https://gitlab.com/group/subgroup/repo
+    // - https://github.com/owner/repo/tree/branch (and other forge web URLs)
+    // - ssh://git@github.com:2222/owner/repo.git
+    // - git://github.com/owner/repo.git
+    // - file:///srv/git/repo.git
+    // - /srv/git/repo.git
     // - git@github.com:owner/repo.git
+    // - gitolite@host:repo
     // - github.com/owner/repo
     // - owner/repo (assume github.com)
 
-    if url.starts_with("https://") || url.starts_with("http://") {
-        parse_https_url(url)
-    } else if url.starts_with("git@") {
+    if let Some((scheme, rest)) = url.split_once("://") {
+        match scheme {
+            "https" | "http" => parse_https_url(url),
+            "ssh" | "git" | "git+ssh" | "ssh+git" => parse_scheme_url(url, scheme, rest),
+            "file" => parse_file_url(url, rest),
+            _ => Err(anyhow::anyhow!("Unsupported URL scheme: {}", scheme)),
+        }
+    } else if url.starts_with('/') {
+        // A local path, cloned the same way as a file:// URL
+        parse_file_url(url, url)
+    } else if is_scp_like(url) {
         parse_ssh_url(url)
     } else if url.contains('/') {
         // Handle short formats
@@ -38,7 +70,7 @@ pub fn parse_repo_url(url: &str) -> Result<RepoInfo> {
             // owner/repo format
             parse_short_url(url)
         } else {
-            // host/owner/repo format
+            // host/namespace/repo format
             parse_host_url(url)
         }
     } else {
@@ -46,63 +78,233 @@ pub fn parse_repo_url(url: &str) -> Result<RepoInfo> {
     }
 }
 
+/// Whether `url` is scp-like syntax, `user@host:path`.
+fn is_scp_like(url: &str) -> bool {
+    url.split_once(':')
+        .is_some_and(|(user_host, _)| user_host.contains('@') && !user_host.contains('/'))
+}
+
+/// Split `[user@]host[:port]` into its parts.
+fn split_authority(authority: &str) -> Result<(Option<String>, String, Option<u16>)> {
+    let (user, host_port) = match authority.rsplit_once('@') {
+        Some((user, host_port)) => (Some(user.to_string()), host_port),
+        None => (None, authority),
+    };
+
+    // Bracketed IPv6 hosts contain colons of their own
+    let port_separator = match host_port.rfind(']') {
+        Some(end) => host_port[end..].find(':').map(|i| end + i),
+        None => host_port.rfind(':'),
+    };
+    let (host, port) = match port_separator {
+        Some(i) => {
+            let port = &host_port[i + 1..];
+            let port = port
+                .parse::<u16>()
+                .map_err(|_| anyhow::anyhow!("Invalid port in URL: {}", port))?;
+            (&host_port[..i], Some(port))
+        }
+        None => (host_port, None),
+    };
+
+    check_host(host)?;
+    Ok((user, host.to_string(), port))
+}
+
+/// Reject hosts that would escape the host directory of the clone path.
+fn check_host(host: &str) -> Result<()> {
+    if host.is_empty() {
+        return Err(anyhow::anyhow!("Missing host in URL"));
+    }
+    if matches!(host, "." | "..") || host.contains(['/', '\\']) {
+        return Err(anyhow::anyhow!("Invalid host in URL: {}", host));
+    }
+    Ok(())
+}
+
+/// Split the path of a forge web URL into the repository path and the ref it points at,
+/// together with the path that follows the ref.
+///
+/// Recognizes GitLab `-/tree/<ref>` and `-/blob/<ref>` on any host, and right after
+/// `owner/repo`: GitHub `tree/<ref>` and `blob/<ref>`, Gitea `src/branch/<ref>` (also `tag` and
+/// `commit`), and on bitbucket.org `src/<ref>`. Anything else is part of the repository path,
+/// since GitLab group and project names can be `tree` or `src` too. Query strings and fragments
+/// are dropped.
+fn split_web_path(host: &str, path: &str) -> (String, Option<String>) {
+    let path = path.split(['?', '#']).next().unwrap_or(path);
+    let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+
+    // GitLab separates the project path from everything else with `/-/`
+    if let Some(marker) = components.iter().position(|c| *c == "-") {
+        let reference = match &components[marker + 1..] {
+            ["tree" | "blob", rest @ ..] if !rest.is_empty() => Some(rest.join("/")),
+            _ => None,
+        };
+        return (components[..marker].join("/"), reference);
+    }
+
+    // The other forges have no nested namespaces, so the marker follows `owner/repo`
+    let reference = match components.get(2..).unwrap_or_default() {
+        ["tree" | "blob", rest @ ..] if !rest.is_empty() => Some(rest.join("/")),
+        ["src", "branch" | "tag" | "commit", rest @ ..] if !rest.is_empty() => Some(rest.join("/")),
+        ["src", rest @ ..] if host == "bitbucket.org" && !rest.is_empty() => Some(rest.join("/")),
+        _ => None,
+    };
+    match reference {
+        Some(reference) => (components[..2].join("/"), Some(reference)),
+        None => (components.join("/"), None),
+    }
+}
+
+/// Split the ref of a web URL into a branch and a commit. A ref that starts with a full commit
+/// id, as in permalinks, names that commit, which is checked out after cloning as it can't be
+/// fetched by name.
+fn split_web_ref(reference: Option<String>) -> (Option<String>, Option<String>) {
+    let Some(reference) = reference else {
+        return (None, None);
+    };
+    let first = reference.split('/').next().unwrap_or_default();
+    if gix::ObjectId::from_hex(first.as_bytes()).is_ok() {
+        (None, Some(first.to_string()))
+    } else {
+        (Some(reference), None)
+    }
+}
+
+/// Split a repository path into its namespace (everything but the last component) and name.
+fn split_repo_path(path: &str) -> Result<(String, String)> {
+    let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+    if components.iter().any(|c| matches!(*c, "." | "..")) {
+        return Err(anyhow::anyhow!("Invalid repository path: {}", path));
+    }
+
+    let (name, namespace) = components
+        .split_last()
+        .ok_or_else(|| anyhow::anyhow!("Missing repository path in URL"))?;
+    let name = name.trim_end_matches(".git");
+    if name.is_empty() {
+        return Err(anyhow::anyhow!("Invalid repository path: {}", path));
+    }
+
+    Ok((namespace.join("/"), name.to_string()))
+}
+
 fn parse_https_url(url: &str) -> Result<RepoInfo> {
-    let url = url
-        .trim_start_matches("https://")
-        .trim_start_matches("http://");
-    
-    let mut parts = url.splitn(3, '/');
-    let host = parts.next().ok_or_else(|| anyhow::anyhow!("Invalid HTTPS URL format"))?;
-    let owner = parts.next().ok_or_else(|| anyhow::anyhow!("Invalid HTTPS URL format"))?;
-    let name = parts.next().ok_or_else(|| anyhow::anyhow!("Invalid HTTPS URL format"))?;
-    let name = name.split('/').next().unwrap_or(name).trim_end_matches(".git");
+    let (scheme, url) = url.split_once("://").ok_or_else(|| anyhow::anyhow!("Invalid HTTPS URL format"))?;
+    let (authority, path) = url.split_once('/').ok_or_else(|| anyhow::anyhow!("Invalid HTTPS URL format"))?;
+    let (user, host, port) = split_authority(authority)?;
+    let (path, reference) = split_web_path(&host, path);
+    let (branch, commit) = split_web_ref(reference);
+    let (namespace, name) = split_repo_path(&path)?;
+    if namespace.is_empty() {
+        return Err(anyhow::anyhow!("Invalid HTTPS URL format"));
+    }
 
     Ok(RepoInfo {
-        host: host.to_string(),
-        owner: owner.to_string(),
-        name: name.to_string(),
-        full_url: format!("https://{}/{}/{}.git", host, owner, name),
+        host,
+        full_url: format!("{}://{}/{}/{}.git", scheme, authority, namespace, name),
+        namespace,
+        name,
+        scheme: Some(scheme.to_string()),
+        user,
+        port,
+        branch,
+        commit,
+    })
+}
+
+fn parse_scheme_url(url: &str, scheme: &str, rest: &str) -> Result<RepoInfo> {
+    let (authority, path) = rest
+        .split_once('/')
+        .ok_or_else(|| anyhow::anyhow!("Invalid {} URL format", scheme))?;
+    let (user, host, port) = split_authority(authority)?;
+    let (namespace, name) = split_repo_path(path)?;
+
+    Ok(RepoInfo {
+        host,
+        namespace,
+        name,
+        full_url: url.trim_end_matches('/').to_string(),
+        scheme: Some(if scheme == "git" { "git" } else { "ssh" }.to_string()),
+        user,
+        port,
+        branch: None,
+        commit: None,
+    })
+}
+
+fn parse_file_url(url: &str, rest: &str) -> Result<RepoInfo> {
+    let (host, path) = rest
+        .split_once('/')
+        .ok_or_else(|| anyhow::anyhow!("Invalid file URL format"))?;
+    if !host.is_empty() {
+        check_host(host)?;
+    }
+    let (namespace, name) = split_repo_path(path)?;
+
+    Ok(RepoInfo {
+        host: if host.is_empty() { "localhost" } else { host }.to_string(),
+        namespace,
+        name,
+        full_url: url.trim_end_matches('/').to_string(),
+        scheme: Some("file".to_string()),
+        user: None,
+        port: None,
+        branch: None,
+        commit: None,
     })
 }
 
 fn parse_ssh_url(url: &str) -> Result<RepoInfo> {
-    let url = url.trim_start_matches("git@");
+    let (user, url) = url.split_once('@').ok_or_else(|| anyhow::anyhow!("Invalid SSH URL format"))?;
     let (host, path) = url.split_once(':').ok_or_else(|| anyhow::anyhow!("Invalid SSH URL format"))?;
-    let (owner, name) = path.split_once('/').ok_or_else(|| anyhow::anyhow!("Invalid SSH URL path format"))?;
-    let name = name.trim_end_matches(".git");
+    check_host(host)?;
+    let (namespace, name) = split_repo_path(path)?;
+    let path = path.trim_end_matches('/').trim_end_matches(".git");
 
     Ok(RepoInfo {
         host: host.to_string(),
-        owner: owner.to_string(),
-        name: name.to_string(),
-        full_url: format!("git@{}:{}/{}.git", host, owner, name),
+        namespace,
+        name,
+        full_url: format!("{}@{}:{}.git", user, host, path),
+        scheme: Some("ssh".to_string()),
+        user: Some(user.to_string()),
+        port: None,
+        branch: None,
+        commit: None,
     })
 }
 
 fn parse_short_url(url: &str) -> Result<RepoInfo> {
-    let (owner, name) = url.split_once('/').ok_or_else(|| anyhow::anyhow!("Invalid short URL format"))?;
-    let name = name.trim_end_matches(".git");
+    let (namespace, name) = split_repo_path(url)?;
+    if namespace.is_empty() {
+        return Err(anyhow::anyhow!("Invalid short URL format"));
+    }
 
     Ok(RepoInfo {
         host: String::new(),
-        owner: owner.to_string(),
-        name: name.to_string(),
+        namespace,
+        name,
         full_url: String::new(),
+        ..Default::default()
     })
 }
 
 fn parse_host_url(url: &str) -> Result<RepoInfo> {
-    let mut parts = url.splitn(3, '/');
-    let host = parts.next().ok_or_else(|| anyhow::anyhow!("Invalid host URL format"))?;
-    let owner = parts.next().ok_or_else(|| anyhow::anyhow!("Invalid host URL format"))?;
-    let name = parts.next().ok_or_else(|| anyhow::anyhow!("Invalid host URL format"))?;
-    let name = name.split('/').next().unwrap_or(name).trim_end_matches(".git");
+    let (host, path) = url.split_once('/').ok_or_else(|| anyhow::anyhow!("Invalid host URL format"))?;
+    check_host(host)?;
+    let (path, reference) = split_web_path(host, path);
+    let (branch, commit) = split_web_ref(reference);
+    let (namespace, name) = split_repo_path(&path)?;
 
     Ok(RepoInfo {
         host: host.to_string(),
-        owner: owner.to_string(),
-        name: name.to_string(),
-        full_url: format!("https://{}/{}/{}.git", host, owner, name),
+        full_url: format!("https://{}/{}/{}.git", host, namespace, name),
+        namespace,
+        name,
+        branch,
+        commit,
+        ..Default::default()
     })
 }
 
@@ -114,7 +316,7 @@ mod tests {
     fn test_parse_https_url() {
         let info = parse_repo_url("https://github.com/rust-lang/rust").unwrap();
         assert_eq!(info.host, "github.com");
-        assert_eq!(info.owner, "rust-lang");
+        assert_eq!(info.namespace, "rust-lang");
         assert_eq!(info.name, "rust");
     }
 
@@ -122,7 +324,7 @@ mod tests {
     fn test_parse_ssh_url() {
         let info = parse_repo_url("git@github.com:rust-lang/rust.git").unwrap();
         assert_eq!(info.host, "github.com");
-        assert_eq!(info.owner, "rust-lang");
+        assert_eq!(info.namespace, "rust-lang");
         assert_eq!(info.name, "rust");
     }
 
@@ -130,7 +332,7 @@ mod tests {
     fn test_parse_short_url() {
         let info = parse_repo_url("rust-lang/rust").unwrap();
         assert_eq!(info.host, "");
-        assert_eq!(info.owner, "rust-lang");
+        assert_eq!(info.namespace, "rust-lang");
         assert_eq!(info.name, "rust");
     }
 
@@ -138,8 +340,146 @@ mod tests {
     fn test_parse_host_url() {
         let info = parse_repo_url("gitlab.com/owner/repo").unwrap();
         assert_eq!(info.host, "gitlab.com");
-        assert_eq!(info.owner, "owner");
+        assert_eq!(info.namespace, "owner");
         assert_eq!(info.name, "repo");
     }
-}
 
+    #[test]
+    fn test_parse_ssh_scheme_url_with_port() {
+        let info = parse_repo_url("ssh://git@example.com:2222/owner/repo.git").unwrap();
+        assert_eq!(info.host, "example.com");
+        assert_eq!(info.namespace, "owner");
+        assert_eq!(info.name, "repo");
+        assert_eq!(info.user.as_deref(), Some("git"));
+        assert_eq!(info.port, Some(2222));
+        assert_eq!(info.scheme.as_deref(), Some("ssh"));
+        assert_eq!(info.full_url, "ssh://git@example.com:2222/owner/repo.git");
+    }
+
+    #[test]
+    fn test_parse_git_url() {
+        let info = parse_repo_url("git://example.com/owner/repo.git").unwrap();
+        assert_eq!(info.host, "example.com");
+        assert_eq!(info.namespace, "owner");
+        assert_eq!(info.name, "repo");
+        assert_eq!(info.user, None);
+        assert_eq!(info.port, None);
+        assert_eq!(info.full_url, "git://example.com/owner/repo.git");
+    }
+
+    #[test]
+    fn test_parse_file_url() {
+        let info = parse_repo_url("file:///srv/git/project.git").unwrap();
+        assert_eq!(info.host, "localhost");
+        assert_eq!(info.namespace, "srv/git");
+        assert_eq!(info.name, "project");
+        assert_eq!(info.full_url, "file:///srv/git/project.git");
+    }
+
+    #[test]
+    fn test_parse_local_path() {
+        let info = parse_repo_url("/srv/git/project.git").unwrap();
+        assert_eq!(info.host, "localhost");
+        assert_eq!(info.namespace, "srv/git");
+        assert_eq!(info.name, "project");
+        assert_eq!(info.full_url, "/srv/git/project.git");
+    }
+
+    #[test]
+    fn test_parse_scp_url_with_custom_user() {
+        let info = parse_repo_url("gitolite@git.example.com:repo").unwrap();
+        assert_eq!(info.host, "git.example.com");
+        assert_eq!(info.namespace, "");
+        assert_eq!(info.name, "repo");
+        assert_eq!(info.user.as_deref(), Some("gitolite"));
+        assert_eq!(info.full_url, "gitolite@git.example.com:repo.git");
+    }
+
+    #[test]
+    fn test_parse_https_url_with_user_and_port() {
+        let info = parse_repo_url("https://me@git.example.com:8443/owner/repo").unwrap();
+        assert_eq!(info.host, "git.example.com");
+        assert_eq!(info.user.as_deref(), Some("me"));
+        assert_eq!(info.port, Some(8443));
+        assert_eq!(info.full_url, "https://me@git.example.com:8443/owner/repo.git");
+    }
+
+    #[test]
+    fn test_parse_rejects_path_traversal() {
+        assert!(parse_repo_url("ssh://example.com/../../etc/repo").is_err());
+        assert!(parse_repo_url("ftp://example.com/owner/repo").is_err());
+        assert!(parse_repo_url("../repo").is_err());
+        assert!(parse_repo_url("owner/..").is_err());
+        assert!(parse_repo_url("../../etc/x").is_err());
+        assert!(parse_repo_url("../etc/x").is_err());
+        assert!(parse_repo_url("host:../..").is_err());
+        assert!(parse_repo_url("git@..:owner/repo").is_err());
+        assert!(parse_repo_url("https://../owner/repo").is_err());
+    }
+
+    #[test]
+    fn test_parse_nested_namespace() {
+        let info = parse_repo_url("https://gitlab.com/group/sub/repo.git").unwrap();
+        assert_eq!(info.namespace, "group/sub");
+        assert_eq!(info.name, "repo");
+        assert_eq!(info.full_url, "https://gitlab.com/group/sub/repo.git");
+
+        let info = parse_repo_url("gitlab.com/group/sub/deeper/repo").unwrap();
+        assert_eq!(info.namespace, "group/sub/deeper");
+        assert_eq!(info.name, "repo");
+        assert_eq!(
+            info.get_clone_path("/base"),
+            PathBuf::from("/base/gitlab.com/group/sub/deeper/repo")
+        );
+
+        let info = parse_repo_url("git@gitlab.com:group/sub/repo.git").unwrap();
+        assert_eq!(info.namespace, "group/sub");
+        assert_eq!(info.full_url, "git@gitlab.com:group/sub/repo.git");
+    }
+
+    #[test]
+    fn test_parse_forge_web_urls() {
+        // Where a ref with slashes ends is left to the clone, which knows the remote's refs
+        let cases = [
+            ("https://github.com/owner/repo/tree/feature/x", "owner", Some("feature/x")),
+            ("https://github.com/owner/repo/tree/master/docs", "owner", Some("master/docs")),
+            ("https://github.com/owner/repo/blob/main/src/lib.rs#L10", "owner", Some("main/src/lib.rs")),
+            ("https://gitlab.com/group/sub/repo/-/tree/dev?ref_type=heads", "group/sub", Some("dev")),
+            ("https://gitlab.com/group/sub/repo/-/blob/dev/README.md", "group/sub", Some("dev/README.md")),
+            ("https://gitlab.com/group/sub/repo/-/merge_requests", "group/sub", None),
+            ("https://gitea.com/owner/repo/src/branch/release/docs", "owner", Some("release/docs")),
+            ("https://bitbucket.org/owner/repo/src/v1.0/README.md", "owner", Some("v1.0/README.md")),
+            ("github.com/owner/repo/tree/main", "owner", Some("main")),
+            ("github.com/owner/repo/tree/abc1234", "owner", Some("abc1234")),
+            ("https://gitlab.com/org/tree/repo", "org/tree", None),
+            ("https://gitlab.com/org/team/src/repo", "org/team/src", None),
+            ("https://gitlab.com/org/blob/src/repo/-/tree/main", "org/blob/src", Some("main")),
+        ];
+
+        for (url, namespace, branch) in cases {
+            let info = parse_repo_url(url).unwrap();
+            assert_eq!(info.namespace, namespace, "{}", url);
+            assert_eq!(info.name, "repo", "{}", url);
+            assert_eq!(info.branch.as_deref(), branch, "{}", url);
+            assert_eq!(info.commit, None, "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_parse_forge_permalinks() {
+        let commit = "0123456789abcdef0123456789abcdef01234567";
+        let urls = [
+            format!("https://github.com/owner/repo/blob/{}/README.md", commit),
+            format!("https://github.com/owner/repo/tree/{}/docs", commit),
+            format!("https://gitlab.com/owner/repo/-/blob/{}/README.md#L3", commit),
+            format!("https://gitea.com/owner/repo/src/commit/{}/docs", commit),
+        ];
+
+        for url in urls {
+            let info = parse_repo_url(&url).unwrap();
+            assert!(info.full_url.ends_with("/owner/repo.git"), "{}", url);
+            assert_eq!(info.branch, None, "{}", url);
+            assert_eq!(info.commit.as_deref(), Some(commit), "{}", url);
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use anyhow::{Context, Result};
+use gix::bstr::{BStr, BString, ByteSlice};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// Move the working tree and index to commit `new_id`, assuming the working tree is clean.
+///
+/// Files are written by gix's checkout, so attributes, filters and `core.autocrlf` apply. Entries
+/// that don't change keep their stat data, and skip-worktree entries as well as files outside a
+/// cone mode sparse checkout stay out of the working tree. Fails before touching anything if an
+/// untracked file is in the way.
+pub(crate) fn update_worktree(repo: &gix::Repository, new_id: gix::ObjectId) -> Result<()> {
+    use gix::index::entry::Flags;
+
+    let workdir = repo.workdir().context("Repository has no working tree")?;
+    let old_index = repo.index_or_empty()?;
+    let new_tree = repo.find_commit(new_id)?.tree_id()?;
+    let mut index = repo.index_from_tree(&new_tree)?;
+    let sparse = sparse_dirs(repo);
+    let outside_cone = |path: &BStr| !sparse.is_empty() && !in_sparse_cone(path, &sparse);
+
+    // Tracked files in the working tree that aren't part of `new_id` as they are
+    let mut obsolete: HashSet<BString> = old_index
+        .entries_with_paths_by_filter_map(|_, entry| (!entry.flags.contains(Flags::SKIP_WORKTREE)).then_some(()))
+        .map(|(path, ())| path.to_owned())
+        .collect();
+    let mut unchanged = Vec::new();
+    let mut added = Vec::new();
+
+    for (position, (entry, path)) in index.entries_mut_with_paths().enumerate() {
+        match old_index.entry_by_path(path) {
+            Some(old) if old.flags.contains(Flags::SKIP_WORKTREE) => {
+                entry.flags.insert(Flags::SKIP_WORKTREE | Flags::EXTENDED);
+            }
+            _ if outside_cone(path) => entry.flags.insert(Flags::SKIP_WORKTREE | Flags::EXTENDED),
+            Some(old) if old.id == entry.id && old.mode == entry.mode => {
+                entry.stat = old.stat;
+                entry.flags.insert(Flags::SKIP_WORKTREE);
+                obsolete.remove(path);
+                unchanged.push(position);
+            }
+            Some(_) => {}
+            None => added.push(path.to_owned()),
+        }
+    }
+
+    for path in &added {
+        let full_path = workdir.join(gix::path::from_bstr(path.as_bstr()));
+        let blocked = match full_path.symlink_metadata() {
+            Ok(metadata) if metadata.is_dir() => !only_tracked(&full_path, path.as_bstr(), &obsolete),
+            Ok(_) => true,
+            Err(_) => path.rfind_iter("/").map(|i| path[..i].as_bstr()).any(|parent| {
+                let metadata = workdir.join(gix::path::from_bstr(parent)).symlink_metadata();
+                metadata.is_ok_and(|metadata| !metadata.is_dir()) && !obsolete.contains(parent)
+            }),
+        };
+        if blocked {
+            return Err(anyhow::anyhow!(
+                "Untracked file would be overwritten: {}",
+                full_path.display()
+            ));
+        }
+    }
+
+    for path in &obsolete {
+        remove_entry(workdir, &workdir.join(gix::path::from_bstr(path.as_bstr())))?;
+    }
+
+    let mut options = repo.checkout_options(gix::worktree::stack::state::attributes::Source::IdMapping)?;
+    options.destination_is_initially_empty = false;
+    options.overwrite_existing = false;
+    let outcome = gix::worktree::state::checkout(
+        &mut index,
+        workdir,
+        repo.objects.clone().into_arc()?,
+        &gix::progress::Discard,
+        &gix::progress::Discard,
+        &gix::interrupt::IS_INTERRUPTED,
+        options,
+    )?;
+    if let Some(collision) = outcome.collisions.first() {
+        return Err(anyhow::anyhow!("Failed to check out {}", collision.path));
+    }
+    if let Some(error) = outcome.errors.first() {
+        return Err(anyhow::anyhow!("Failed to check out {}: {}", error.path, error.error));
+    }
+
+    let entries = index.entries_mut();
+    for position in unchanged {
+        entries[position].flags.remove(Flags::SKIP_WORKTREE);
+    }
+    index.write(Default::default())?;
+    Ok(())
+}
+
+/// Whether the directory at `dir` (at `path` in the working tree) holds nothing but files in
+/// `tracked`.
+fn only_tracked(dir: &Path, path: &BStr, tracked: &HashSet<BString>) -> bool {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return false;
+    };
+    entries.into_iter().all(|entry| {
+        let Ok(entry) = entry else {
+            return false;
+        };
+        let name = entry.file_name();
+        let mut child = path.to_owned();
+        child.push(b'/');
+        child.extend_from_slice(&gix::path::into_bstr(Path::new(&name)));
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => only_tracked(&entry.path(), child.as_bstr(), tracked),
+            Ok(_) => tracked.contains(&child),
+            Err(_) => false,
+        }
+    })
+}
+
+/// Remove `path` and any parent directories it leaves empty, stopping at `workdir`.
+fn remove_entry(workdir: &Path, path: &Path) -> Result<()> {
+    if path.symlink_metadata().is_ok() {
+        fs::remove_file(path)?;
+    }
+
+    let mut dir = path.parent();
+    while let Some(current) = dir {
+        if current == workdir || fs::remove_dir(current).is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+    Ok(())
+}
+
+/// The directories of a cone mode sparse checkout, leaving out the parents git adds for them.
+pub(crate) fn sparse_dirs(repo: &gix::Repository) -> Vec<String> {
+    if repo.config_snapshot().boolean("core.sparseCheckout") != Some(true) {
+        return Vec::new();
+    }
+
+    let patterns = fs::read_to_string(repo.git_dir().join("info").join("sparse-checkout")).unwrap_or_default();
+    let patterns: Vec<&str> = patterns.lines().map(str::trim).collect();
+    patterns
+        .iter()
+        .filter_map(|pattern| pattern.strip_prefix('/')?.strip_suffix('/'))
+        .filter(|dir| *dir != "*" && !patterns.contains(&format!("!/{}/*/", dir).as_str()))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Whether the file at `path` is checked out by a cone mode sparse checkout of `dirs`.
+pub(crate) fn in_sparse_cone(path: &BStr, dirs: &[String]) -> bool {
+    let Some(parent) = path.rfind_byte(b'/').map(|i| &path[..i]) else {
+        return true;
+    };
+
+    dirs.iter().map(|dir| dir.as_bytes()).any(|dir| {
+        let within = |outer: &[u8], inner: &[u8]| inner.starts_with(outer) && inner.get(outer.len()) == Some(&b'/');
+        parent == dir || within(dir, parent) || within(parent, dir)
+    })
+}
+
+/// The `info/sparse-checkout` contents git writes in cone mode for `dirs`.
+pub(crate) fn sparse_cone_patterns(dirs: &[String]) -> String {
+    let mut patterns = vec!["/*".to_string(), "!/*/".to_string()];
+    let mut dirs = dirs.to_vec();
+    dirs.sort();
+
+    for dir in &dirs {
+        let components: Vec<&str> = dir.split('/').collect();
+        for depth in 1..components.len() {
+            let parent = components[..depth].join("/");
+            for pattern in [format!("/{}/", parent), format!("!/{}/*/", parent)] {
+                if !patterns.contains(&pattern) {
+                    patterns.push(pattern);
+                }
+            }
+        }
+        patterns.push(format!("/{}/", dir));
+    }
+
+    patterns.join("\n") + "\n"
+}